@@ -0,0 +1,85 @@
+// Decoding time of Reddit's responses, on threads and listings
+// made large out of the recorded fixtures of the integration tests.
+// Each is decoded in place, and through a `Value` first as responses used to be, to compare.
+//
+//     cargo bench --bench deserialize
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use older_reddit::api_result_types::ApiData;
+use serde_json::{json, Value};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/snapshots");
+
+fn fixture(path: &str) -> Value {
+    let json = std::fs::read_to_string(format!("{}/{}", FIXTURES, path)).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+fn listing(children: Vec<Value>) -> Value {
+    json!({"kind": "Listing", "data": {"after": null, "before": null, "dist": null, "children": children}})
+}
+
+/// The recorded thread with `width` top-level comments, each starting a chain `depth` comments deep.
+fn thread(width: usize, depth: usize) -> Vec<u8> {
+    let mut thread = fixture("r/rust/comments/1abcde.json");
+    let comment = thread[1]["data"]["children"][0].clone();
+
+    let reply_chain = |i: usize| {
+        // the deepest reply has none, which Reddit sends as ""
+        let mut replies = json!("");
+        for d in (0..depth).rev() {
+            let mut reply = comment.clone();
+            reply["data"]["id"] = json!(format!("c{}x{}", i, d));
+            reply["data"]["replies"] = replies;
+            replies = listing(vec![reply]);
+        }
+        replies["data"]["children"][0].take()
+    };
+
+    let comments = (0..width).map(reply_chain).collect();
+    thread[1] = listing(comments);
+
+    serde_json::to_vec(&thread).unwrap()
+}
+
+/// The recorded listing with its posts repeated up to `posts`.
+fn subreddit(posts: usize) -> Vec<u8> {
+    let mut listing = fixture("r/rust/%2Ejson");
+    let children = listing["data"]["children"].as_array().unwrap().clone();
+
+    listing["data"]["children"] = children.into_iter().cycle().take(posts).collect();
+    serde_json::to_vec(&listing).unwrap()
+}
+
+fn deserialize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deserialize");
+
+    let cases = [
+        ("listing of 100 posts", subreddit(100)),
+        ("thread 500 wide, 4 deep", thread(500, 4)),
+        // as deep as Reddit goes before "continue this thread"
+        ("thread 200 wide, 10 deep", thread(200, 10)),
+    ];
+
+    for (name, json) in &cases {
+        group.throughput(Throughput::Bytes(json.len() as u64));
+        group.bench_with_input(BenchmarkId::new("in place", name), json, |b, json| {
+            b.iter(|| serde_json::from_slice::<ApiData>(black_box(json)).unwrap())
+        });
+        group.bench_with_input(
+            BenchmarkId::new("through a Value", name),
+            json,
+            |b, json| {
+                b.iter(|| {
+                    let value: Value = serde_json::from_slice(black_box(json)).unwrap();
+                    serde_json::from_value::<ApiData>(value).unwrap()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, deserialize);
+criterion_main!(benches);
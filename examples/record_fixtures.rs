@@ -0,0 +1,65 @@
+// Records the fixtures of the integration tests from Reddit,
+// by opening the pages listed in `tests/fixtures/pages.txt` with `--record` on.
+//
+//     cargo run --example record_fixtures
+
+use std::net::TcpListener;
+
+use older_reddit::{routes, state::AppState, upstream::Client};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+
+    let pages = std::fs::read_to_string(format!("{}/pages.txt", FIXTURES))?;
+
+    let state = AppState::from_env(Client::record(FIXTURES))?;
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+
+    let server = axum::Server::from_tcp(listener)?.serve(routes::router(state).into_make_service());
+    tokio::spawn(server);
+
+    // redirects are recorded, not followed
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+
+    let mut failed = Vec::new();
+
+    for page in pages
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+    {
+        let response = http
+            .get(format!("http://{}{}", addr, page))
+            .header(reqwest::header::USER_AGENT, "older-reddit fixture recorder")
+            .send()
+            .await?;
+
+        let status = response.status();
+        println!("{} {}", status.as_u16(), page);
+
+        // short links answer with a redirect, which is what gets recorded for them
+        if !status.is_success() && !status.is_redirection() {
+            failed.push(page);
+        }
+
+        // Reddit rate limits clients going too fast
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+
+    // what Reddit didn't answer wasn't recorded, the fixtures of these pages are stale
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "{} pages weren't recorded: {}",
+            failed.len(),
+            failed.join(", ")
+        );
+    }
+
+    Ok(())
+}
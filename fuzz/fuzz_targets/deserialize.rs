@@ -0,0 +1,17 @@
+#![no_main]
+
+// Reddit's responses, as the server decodes them, from untrusted bytes.
+// Nothing may panic, and deep nesting (`replies` can go on forever) must be
+// refused before it overflows the stack. Seeded with the recorded fixtures:
+//
+//     cargo +nightly fuzz run deserialize fuzz/corpus/deserialize tests/fixtures/snapshots
+
+use libfuzzer_sys::fuzz_target;
+use older_reddit::api_result_types::{ApiData, EditTimestamp, RedditData, ReplyList};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = serde_json::from_slice::<ApiData>(data);
+    let _ = serde_json::from_slice::<RedditData>(data);
+    let _ = serde_json::from_slice::<ReplyList>(data);
+    let _ = serde_json::from_slice::<EditTimestamp>(data);
+});
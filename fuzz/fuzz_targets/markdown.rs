@@ -0,0 +1,15 @@
+#![no_main]
+
+// Posts and comments as pages, feeds and gemtext render them.
+// Seeded with the recorded fixtures, whose JSON is full of markdown:
+//
+//     cargo +nightly fuzz run markdown fuzz/corpus/markdown tests/fixtures/snapshots
+
+use libfuzzer_sys::fuzz_target;
+use older_reddit::{gemtext, utils};
+
+fuzz_target!(|text: &str| {
+    // the `markdown` filter, with links to Reddit made local as in feeds
+    let _ = askama::filters::markdown(askama::Html, utils::rewrite_reddit_links(text), None);
+    let _ = gemtext::from_markdown(text);
+});
@@ -1,69 +1,73 @@
-use reqwest::{header::USER_AGENT, Client, StatusCode};
+use reqwest::StatusCode;
+use serde::Serialize;
 
 use crate::{
-    api_result_types::{ApiData, RedditData, T1Data, T3Data, WikiPageData, ListingData},
+    api_result_types::{
+        ApiData, GalleryItem, RedditData, ReplyList, SubredditRule, SubredditRulesResponse, T1Data, T2Data, T3Data,
+        T5Data, T6Data, WikiPageData, WikiPageListingData, WikiRevision,
+        WikiRevisionListingResponse,
+    },
     api_types::{
         CommentSortingMode, SearchSortingMode, SearchTimeOrdering, SortingMode, TopSortingTime, UserFilterMode, UserSortingMode,
     },
 };
 
-use crate::utils;
+use crate::{
+    pagination::{PageRequest, Pagination},
+    preferences::Preferences,
+    upstream::Client,
+    utils,
+};
 
 pub struct CommentsQuery {
     pub post: T3Data,
     pub comments: Vec<T1Data>,
-    pub after: Option<String>,
-    pub before: Option<String>,
+    pub pagination: Pagination,
 }
 
 impl CommentsQuery {
-    pub fn before_url(&self) -> Option<&str> {
-        self.before.as_deref()
-    }
-
-    pub fn after_url(&self) -> Option<&str> {
-        self.after.as_deref()
+    pub fn get_post_type(&self) -> PostType {
+        self.post.post_type()
     }
-}
 
-impl CommentsQuery {
-    pub fn get_post_type(&self) -> PostType {
-        if self.post.is_video {
-            return PostType::Video;
+    pub fn get_url(&self) -> Option<String> {
+        if let Some(u) = self.post.url.clone() {
+            if self.post.is_reddit_media_domain {
+                return Some(u.replace("https://i.redd.it", "/i"));
+            }
+            return Some(u);
         }
 
-        if self.post.poll_data.is_some() {
-            return PostType::Poll;
-        }
+        None
+    }
 
-        if self.post.is_gallery.is_some() {
-            return PostType::Gallery;
-        }
+    /// Every comment with how deep it is, in reading order,
+    /// for frontends that can't nest them.
+    pub fn thread(&self) -> Vec<(usize, &T1Data)> {
+        let mut thread = Vec::new();
 
-        if self.post.is_reddit_media_domain {
-            return PostType::Image;
+        for comment in &self.comments {
+            flatten(&mut thread, comment, 0);
         }
 
-        if self.post.url.is_some() && self.post.selftext.len() == 0 {
-            return PostType::Link;
-        }
-
-        return PostType::Text;
+        thread
     }
+}
 
-    pub fn get_url(&self) -> Option<String> {
-        if let Some(u) = self.post.url.clone() {
-            if self.post.is_reddit_media_domain {
-                return Some(u.replace("https://i.redd.it", "/i"));
+fn flatten<'a>(thread: &mut Vec<(usize, &'a T1Data)>, comment: &'a T1Data, depth: usize) {
+    thread.push((depth, comment));
+
+    if let ReplyList::Replies(list) = &comment.replies {
+        for child in &list.children {
+            if let RedditData::T1(reply) = child {
+                flatten(thread, reply, depth + 1);
             }
-            return Some(u);
         }
-
-        return None;
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PostType {
     Text,
     Link,
@@ -73,20 +77,32 @@ pub enum PostType {
     Poll,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn comments(
     client: &Client,
     subreddit: &str,
     post_id: &str,
+    comment_id: Option<&str>,
     sorting: Option<CommentSortingMode>,
+    page: &PageRequest,
+    prefs: &Preferences,
     user_agent: &str,
 ) -> Result<CommentsQuery, StatusCode> {
-    let sort = sorting.unwrap_or_default();
+    let sort = sorting.unwrap_or(prefs.comment_sort);
 
     let mut base = utils::get_reddit_domain();
     base.add_route("r");
     base.add_route(subreddit);
     base.add_route("comments");
-    base.add_route(&format!("{}.json", post_id));
+
+    if let Some(c) = comment_id {
+        // the title part of the permalink can be anything
+        base.add_route(post_id);
+        base.add_route("_");
+        base.add_route(&format!("{}.json", c));
+    } else {
+        base.add_route(&format!("{}.json", post_id));
+    }
 
     match sort {
         CommentSortingMode::Suggested => &mut base,
@@ -98,9 +114,15 @@ pub async fn comments(
         CommentSortingMode::Top => base.add_param("sort", "top"),
     };
 
+    if let Some(d) = prefs.comment_depth {
+        base.add_param("depth", &d.to_string());
+    }
+
+    page.add_params(&mut base);
+
     let url = base.build();
 
-    let response = match client.get(&url).header(USER_AGENT, user_agent).send().await {
+    let response = match client.get(&url, user_agent).await {
         Ok(r) => {
             if r.status() == StatusCode::OK {
                 r
@@ -114,7 +136,7 @@ pub async fn comments(
         }
     };
 
-    let res = match response.json::<ApiData>().await {
+    let res = match response.json::<ApiData>() {
         Ok(j) => j,
         Err(e) => {
             tracing::error!("{}", e);
@@ -129,31 +151,37 @@ pub async fn comments(
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     };
 
-    if data.len() != 2 {
-        tracing::error!("Wrong schema 2");
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
+    let [first, second]: [RedditData; 2] = match data.try_into() {
+        Ok(d) => d,
+        Err(_) => {
+            tracing::error!("Wrong schema 2");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
 
-    let first_listing = if let RedditData::Listing(l) = &data[0] {
+    let first_listing = if let RedditData::Listing(l) = first {
         l
     } else {
         tracing::error!("Wrong schema 3");
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     };
 
-    if first_listing.children.len() != 1 {
-        tracing::error!("Wrong schema 4");
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
-    }
+    let [first_child]: [RedditData; 1] = match first_listing.children.try_into() {
+        Ok(c) => c,
+        Err(_) => {
+            tracing::error!("Wrong schema 4");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
 
-    let post = if let RedditData::T3(t3) = &first_listing.children[0] {
-        t3.clone()
+    let post = if let RedditData::T3(t3) = first_child {
+        t3
     } else {
         tracing::error!("Wrong schema 5");
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     };
 
-    let second_listing = if let RedditData::Listing(list) = &data[1] {
+    let second_listing = if let RedditData::Listing(list) = second {
         list
     } else {
         tracing::error!("Wrong schema 6");
@@ -172,7 +200,6 @@ pub async fn comments(
 
     let comments: Vec<T1Data> = second_listing
         .children
-        .clone()
         .into_iter()
         .filter_map(|child| match child {
             RedditData::T1(t1_data) => Some(t1_data),
@@ -180,31 +207,88 @@ pub async fn comments(
         })
         .collect();
 
-    return Ok(CommentsQuery {
+    let pagination = Pagination::new(
+        page,
+        second_listing.before,
+        second_listing.after,
+        comments.len(),
+    );
+
+    Ok(CommentsQuery {
         post,
         comments,
-        after: second_listing.after.clone(),
-        before: second_listing.before.clone(),
-    });
+        pagination,
+    })
 }
 
 pub struct SubredditQuery {
     pub posts: Vec<T3Data>,
-    pub after: Option<String>,
-    pub before: Option<String>,
+    pub pagination: Pagination,
+    /// Posts removed by filters
+    pub hidden: usize,
 }
 
-impl SubredditQuery {
-    pub fn before_url(&self) -> Option<&str> {
-        self.before.as_deref()
+impl T3Data {
+    pub fn post_type(&self) -> PostType {
+        if self.is_video {
+            return PostType::Video;
+        }
+
+        if self.poll_data.is_some() {
+            return PostType::Poll;
+        }
+
+        if self.is_gallery.is_some() {
+            return PostType::Gallery;
+        }
+
+        if self.is_reddit_media_domain {
+            return PostType::Image;
+        }
+
+        if self.url.is_some() && self.selftext.is_empty() {
+            return PostType::Link;
+        }
+
+        PostType::Text
     }
 
-    pub fn after_url(&self) -> Option<&str> {
-        self.after.as_deref()
+    /// Local URL of the listing thumbnail, if there is one that can be shown.
+    pub fn thumbnail_url(&self) -> Option<String> {
+        if self.hides_thumbnail() {
+            return None;
+        }
+
+        self.thumbnail
+            .as_deref()?
+            .strip_prefix("https://b.thumbs.redditmedia.com/")
+            .map(|f| format!("/t/{}", f))
+    }
+
+    /// NSFW and spoiler thumbnails give away what they hide.
+    pub fn hides_thumbnail(&self) -> bool {
+        let has_thumbnail = self
+            .thumbnail
+            .as_deref()
+            .is_some_and(|t| !matches!(t, "" | "self" | "default"));
+
+        has_thumbnail && (self.over_18 || self.spoiler)
+    }
+
+    /// File of a gallery item on `i.redd.it`, named after its media type.
+    /// Items Reddit doesn't say the type of are JPEGs, like most are.
+    pub fn gallery_file(&self, item: &GalleryItem) -> String {
+        let extension = self
+            .media_metadata
+            .as_ref()
+            .and_then(|m| m.get(&item.media_id))
+            .and_then(|m| m.m.as_deref())
+            .and_then(|m| m.strip_prefix("image/"))
+            .map_or("jpg", |e| if e == "jpeg" { "jpg" } else { e });
+
+        format!("{}.{}", item.media_id, extension)
     }
-}
 
-impl T3Data {
     pub fn get_author_flair(&self) -> Option<(&str, &str)> {
         match &self.author_flair_text {
             Some(t) => match &self.author_flair_background_color {
@@ -227,6 +311,15 @@ impl T3Data {
 }
 
 impl T1Data {
+    /// Local link to the post the comment is in
+    pub fn post_url(&self) -> String {
+        format!(
+            "/r/{}/comments/{}",
+            self.subreddit,
+            self.link_id.trim_start_matches("t3_")
+        )
+    }
+
     pub fn get_author_flair(&self) -> Option<(&str, &str)> {
         match &self.author_flair_text {
             Some(t) => match &self.author_flair_background_color {
@@ -244,10 +337,11 @@ pub async fn subreddit(
     subreddit: &str,
     sorting: Option<SortingMode>,
     top_time: Option<TopSortingTime>,
-    after: Option<&str>,
+    page: &PageRequest,
+    prefs: &Preferences,
     user_agent: &str,
 ) -> Result<SubredditQuery, StatusCode> {
-    let sort = sorting.unwrap_or_default();
+    let sort = sorting.unwrap_or(prefs.sort);
 
     let mut base = utils::get_reddit_domain();
     base.add_route("r");
@@ -273,15 +367,11 @@ pub async fn subreddit(
         }
     };
 
-    let mut after_base = base.clone();
-
-    if let Some(s) = after {
-        base.add_param("after", s);
-    }
+    page.add_params(&mut base);
 
     let url = base.build();
 
-    let response = match client.get(url).header(USER_AGENT, user_agent).send().await {
+    let response = match client.get(&url, user_agent).await {
         Ok(r) => {
             if r.status() == StatusCode::OK {
                 r
@@ -295,7 +385,7 @@ pub async fn subreddit(
         }
     };
 
-    let res = match response.json::<ApiData>().await {
+    let res = match response.json::<ApiData>() {
         Ok(j) => j,
         Err(e) => {
             tracing::error!("{}", e);
@@ -329,44 +419,119 @@ pub async fn subreddit(
         })
         .collect();
 
-        let before = match listing.before {
-            Some(b) => {
-                let mut before_base = after_base.clone();
-                before_base.add_param("before", &b);
-                Some(before_base.build())
-            }
-            _ => None,
-        };
-    
-        let after = match listing.after {
-            Some(b) => {
-                after_base.add_param("after", &b);
-                Some(after_base.build())
-            }
-            _ => None,
-        };
+    let pagination = Pagination::new(page, listing.before, listing.after, posts.len());
 
     Ok(SubredditQuery {
         posts,
-        after: after,
-        before: before,
+        pagination,
+        hidden: 0,
     })
 }
 
+impl T5Data {
+    pub fn created(&self) -> String {
+        utils::format_timestamp(&(self.created_utc as u64))
+    }
+
+    pub fn is_nsfw(&self) -> bool {
+        self.over18.unwrap_or_default()
+    }
+}
+
+pub async fn subreddit_about(
+    client: &Client,
+    subreddit: &str,
+    user_agent: &str,
+) -> Result<T5Data, StatusCode> {
+    let mut base = utils::get_reddit_domain();
+    base.add_route("r");
+    base.add_route(subreddit);
+    base.add_route("about.json");
+
+    let url = base.build();
+
+    let response = match client.get(&url, user_agent).await {
+        Ok(r) => {
+            if r.status() == StatusCode::OK {
+                r
+            } else {
+                return Err(r.status());
+            }
+        }
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let res = match response.json::<ApiData>() {
+        Ok(j) => j,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if let ApiData::Single(RedditData::T5(t5)) = res {
+        Ok(t5)
+    } else {
+        tracing::error!("Invalid schema");
+        Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+pub async fn subreddit_rules(
+    client: &Client,
+    subreddit: &str,
+    user_agent: &str,
+) -> Result<Vec<SubredditRule>, StatusCode> {
+    let mut base = utils::get_reddit_domain();
+    base.add_route("r");
+    base.add_route(subreddit);
+    base.add_route("about");
+    base.add_route("rules.json");
+
+    let url = base.build();
+
+    let response = match client.get(&url, user_agent).await {
+        Ok(r) => {
+            if r.status() == StatusCode::OK {
+                r
+            } else {
+                return Err(r.status());
+            }
+        }
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    match response.json::<SubredditRulesResponse>() {
+        Ok(j) => Ok(j.rules),
+        Err(e) => {
+            tracing::error!("{}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 // ?after=t3_16kksoi
+#[allow(clippy::too_many_arguments)]
 pub async fn search(
     client: &Client,
     subreddit: &str,
     query: &str,
     sorting: Option<SearchSortingMode>,
     time_ordering: Option<SearchTimeOrdering>,
-    after: Option<&str>,
+    page: &PageRequest,
     include_over_18: bool,
     only_current_subreddit: bool,
+    prefs: &Preferences,
     user_agent: &str,
 ) -> Result<SubredditQuery, StatusCode> {
-    let sort = sorting.unwrap_or_default();
-    let order = time_ordering.unwrap_or_default();
+    let sort = sorting.unwrap_or(prefs.search_sort);
+    let order = time_ordering.unwrap_or(prefs.search_time);
 
     let mut base = utils::get_reddit_domain();
     base.add_route("r");
@@ -394,19 +559,15 @@ pub async fn search(
         base.add_param("restrict_sr", "on");
     }
 
-    if include_over_18 {
+    if include_over_18 && !prefs.hide_nsfw {
         base.add_param("include_over_18", "on");
     }
 
-    let mut after_base = base.clone();
-
-    if let Some(s) = after {
-        base.add_param("after", s);
-    }
+    page.add_params(&mut base);
 
     let url = base.build();
 
-    let response = match client.get(url).header(USER_AGENT, user_agent).send().await {
+    let response = match client.get(&url, user_agent).await {
         Ok(r) => {
             if r.status() == StatusCode::OK {
                 r
@@ -420,7 +581,7 @@ pub async fn search(
         }
     };
 
-    let res = match response.json::<ApiData>().await {
+    let res = match response.json::<ApiData>() {
         Ok(j) => j,
         Err(e) => {
             tracing::error!("{}", e);
@@ -454,38 +615,21 @@ pub async fn search(
         })
         .collect();
 
-
-    let before = match listing.before {
-        Some(b) => {
-            let mut before_base = after_base.clone();
-            before_base.add_param("before", &b);
-            Some(before_base.build())
-        }
-        _ => None,
-    };
-
-    let after = match listing.after {
-        Some(b) => {
-            after_base.add_param("after", &b);
-            Some(after_base.build())
-        }
-        _ => None,
-    };
+    let pagination = Pagination::new(page, listing.before, listing.after, posts.len());
 
     Ok(SubredditQuery {
         posts,
-        after: after,
-        before: before,
+        pagination,
+        hidden: 0,
     })
 }
 
 impl WikiPageData {
-    pub fn before_url(&self) -> Option<&str> {
-        None
-    }
-
-    pub fn after_url(&self) -> Option<&str> {
-        None
+    pub fn revision_author(&self) -> Option<&str> {
+        match self.revision_by.as_deref() {
+            Some(RedditData::T2(t2)) => Some(&t2.name),
+            _ => None,
+        }
     }
 }
 
@@ -493,6 +637,7 @@ pub async fn wiki(
     client: &Client,
     subreddit: &str,
     path: Option<&str>,
+    revision: Option<&str>,
     user_agent: &str,
 ) -> Result<WikiPageData, StatusCode> {
     let mut base = utils::get_reddit_domain();
@@ -508,9 +653,13 @@ pub async fn wiki(
 
     base.add_route(".json");
 
+    if let Some(v) = revision {
+        base.add_param("v", v);
+    }
+
     let url = base.build();
 
-    let response = match client.get(url).header(USER_AGENT, user_agent).send().await {
+    let response = match client.get(&url, user_agent).await {
         Ok(r) => {
             if r.status() == StatusCode::OK {
                 r
@@ -524,7 +673,7 @@ pub async fn wiki(
         }
     };
 
-    let res = match response.json::<ApiData>().await {
+    let res = match response.json::<ApiData>() {
         Ok(j) => j,
         Err(e) => {
             tracing::error!("{}", e);
@@ -540,25 +689,144 @@ pub async fn wiki(
     }
 }
 
-impl ListingData {
-    pub fn before_url(&self) -> Option<&str> {
-        self.before.as_deref()
+pub async fn wiki_pages(
+    client: &Client,
+    subreddit: &str,
+    user_agent: &str,
+) -> Result<WikiPageListingData, StatusCode> {
+    let mut base = utils::get_reddit_domain();
+    base.add_route("r");
+    base.add_route(subreddit);
+    base.add_route("wiki");
+    base.add_route("pages.json");
+
+    let url = base.build();
+
+    let response = match client.get(&url, user_agent).await {
+        Ok(r) => {
+            if r.status() == StatusCode::OK {
+                r
+            } else {
+                return Err(r.status());
+            }
+        }
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let res = match response.json::<ApiData>() {
+        Ok(j) => j,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if let ApiData::Single(RedditData::WikiPageListing(l)) = res {
+        Ok(l)
+    } else {
+        tracing::error!("Invalid schema");
+        Err(StatusCode::INTERNAL_SERVER_ERROR)
     }
+}
 
-    pub fn after_url(&self) -> Option<&str> {
-        self.after.as_deref()
+pub struct WikiRevisionsQuery {
+    pub revisions: Vec<WikiRevision>,
+    pub pagination: Pagination,
+}
+
+impl WikiRevision {
+    pub fn date(&self) -> String {
+        utils::format_timestamp(&(self.timestamp as u64))
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        match &self.author {
+            Some(RedditData::T2(t2)) => Some(&t2.name),
+            _ => None,
+        }
     }
 }
 
+/// Revisions of a single page, or of the whole wiki if `path` is `None`.
+pub async fn wiki_revisions(
+    client: &Client,
+    subreddit: &str,
+    path: Option<&str>,
+    page: &PageRequest,
+    user_agent: &str,
+) -> Result<WikiRevisionsQuery, StatusCode> {
+    let mut base = utils::get_reddit_domain();
+    base.add_route("r");
+    base.add_route(subreddit);
+    base.add_route("wiki");
+
+    if let Some(p) = path {
+        base.add_route("revisions");
+        base.add_route(p);
+        base.add_route(".json");
+    } else {
+        base.add_route("revisions.json");
+    }
+
+    page.add_params(&mut base);
+
+    let url = base.build();
+
+    let response = match client.get(&url, user_agent).await {
+        Ok(r) => {
+            if r.status() == StatusCode::OK {
+                r
+            } else {
+                return Err(r.status());
+            }
+        }
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let listing = match response.json::<WikiRevisionListingResponse>() {
+        Ok(j) => j.data,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let pagination = Pagination::new(
+        page,
+        listing.before,
+        listing.after,
+        listing.children.len(),
+    );
+
+    Ok(WikiRevisionsQuery {
+        revisions: listing.children,
+        pagination,
+    })
+}
+
+/// Posts and comments of a user
+pub struct UserQuery {
+    pub children: Vec<RedditData>,
+    pub pagination: Pagination,
+    /// Posts and comments removed by filters
+    pub hidden: usize,
+}
+
 pub async fn user(
     client: &Client,
     username: &str,
     sorting: Option<UserSortingMode>,
     t: Option<SearchTimeOrdering>,
     filtering: Option<UserFilterMode>,
-    after: Option<&str>,
+    page: &PageRequest,
     user_agent: &str,
-) -> Result<ListingData, StatusCode> {
+) -> Result<UserQuery, StatusCode> {
     let sort = sorting.unwrap_or_default();
     let filter = filtering.unwrap_or_default();
 
@@ -590,13 +858,11 @@ pub async fn user(
         },
     };
 
-    if let Some(s) = after {
-        base.add_param("after", s);
-    }
+    page.add_params(&mut base);
 
     let url = base.build();
 
-    let response = match client.get(url).header(USER_AGENT, user_agent).send().await {
+    let response = match client.get(&url, user_agent).await {
         Ok(r) => {
             if r.status() == StatusCode::OK {
                 r
@@ -610,7 +876,7 @@ pub async fn user(
         }
     };
 
-    let res = match response.json::<ApiData>().await {
+    let res = match response.json::<ApiData>() {
         Ok(j) => j,
         Err(e) => {
             tracing::error!("{}", e);
@@ -619,9 +885,188 @@ pub async fn user(
     };
 
     if let ApiData::Single(RedditData::Listing(w)) = res {
-        Ok(w)
+        let pagination = Pagination::new(page, w.before, w.after, w.children.len());
+
+        Ok(UserQuery {
+            children: w.children,
+            pagination,
+            hidden: 0,
+        })
+    } else {
+        tracing::error!("Invalid schema");
+        Err(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl T2Data {
+    pub fn created(&self) -> Option<String> {
+        self.created_utc
+            .map(|c| utils::format_timestamp(&(c as u64)))
+    }
+}
+
+pub async fn user_about(
+    client: &Client,
+    username: &str,
+    user_agent: &str,
+) -> Result<T2Data, StatusCode> {
+    let mut base = utils::get_reddit_domain();
+    base.add_route("user");
+    base.add_route(username);
+    base.add_route("about.json");
+
+    let url = base.build();
+
+    let response = match client.get(&url, user_agent).await {
+        Ok(r) => {
+            if r.status() == StatusCode::OK {
+                r
+            } else {
+                return Err(r.status());
+            }
+        }
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let res = match response.json::<ApiData>() {
+        Ok(j) => j,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if let ApiData::Single(RedditData::T2(t2)) = res {
+        Ok(t2)
     } else {
         tracing::error!("Invalid schema");
         Err(StatusCode::INTERNAL_SERVER_ERROR)
     }
-}
\ No newline at end of file
+}
+
+pub async fn user_trophies(
+    client: &Client,
+    username: &str,
+    user_agent: &str,
+) -> Result<Vec<T6Data>, StatusCode> {
+    let mut base = utils::get_reddit_domain();
+    base.add_route("user");
+    base.add_route(username);
+    base.add_route("trophies.json");
+
+    let url = base.build();
+
+    let response = match client.get(&url, user_agent).await {
+        Ok(r) => {
+            if r.status() == StatusCode::OK {
+                r
+            } else {
+                return Err(r.status());
+            }
+        }
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let res = match response.json::<ApiData>() {
+        Ok(j) => j,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let list = if let ApiData::Single(RedditData::TrophyList(l)) = res {
+        l
+    } else {
+        tracing::error!("Invalid schema");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    };
+
+    Ok(list
+        .trophies
+        .into_iter()
+        .filter_map(|child| match child {
+            RedditData::T6(t6_data) => Some(t6_data),
+            _ => None,
+        })
+        .collect())
+}
+
+const MAX_REDIRECTS: usize = 8;
+
+/// Follows the redirects of a share or short link until it points to a post,
+/// and returns the subreddit and the post id.
+/// `client` must not follow redirects on its own, otherwise the whole thread gets downloaded.
+pub async fn resolve_permalink(
+    client: &Client,
+    url: &str,
+    user_agent: &str,
+) -> Result<(String, String), StatusCode> {
+    let mut url = match reqwest::Url::parse(url) {
+        Ok(u) => u,
+        Err(e) => {
+            tracing::error!("{}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    for _ in 0..MAX_REDIRECTS {
+        if let Some(permalink) = parse_permalink(url.path()) {
+            return Ok(permalink);
+        }
+
+        let response = match client.get(url.as_str(), user_agent).await {
+            Ok(r) => {
+                if r.status().is_redirection() {
+                    r
+                } else if r.status().is_success() {
+                    // we landed on a page that isn't a post
+                    return Err(StatusCode::NOT_FOUND);
+                } else {
+                    return Err(r.status());
+                }
+            }
+            Err(e) => {
+                tracing::error!("{}", e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+
+        let location = match response.location() {
+            Some(l) => l,
+            None => {
+                tracing::error!("Redirect without location");
+                return Err(StatusCode::BAD_GATEWAY);
+            }
+        };
+
+        url = match url.join(location) {
+            Ok(u) => u,
+            Err(e) => {
+                tracing::error!("{}", e);
+                return Err(StatusCode::BAD_GATEWAY);
+            }
+        };
+    }
+
+    tracing::error!("Too many redirects");
+    Err(StatusCode::BAD_GATEWAY)
+}
+
+/// `/r/:subreddit/comments/:id/...`
+fn parse_permalink(path: &str) -> Option<(String, String)> {
+    let mut segments = path.split('/').filter(|s| !s.is_empty());
+
+    match (segments.next(), segments.next(), segments.next(), segments.next()) {
+        (Some("r"), Some(subreddit), Some("comments"), Some(id)) => {
+            Some((subreddit.to_string(), id.to_string()))
+        }
+        _ => None,
+    }
+}
@@ -1,4 +1,13 @@
-use serde::{Deserialize, Deserializer};
+use std::{cell::Cell, collections::HashMap, fmt};
+
+use serde::{
+    de::{
+        self,
+        value::{MapAccessDeserializer, SeqAccessDeserializer},
+        DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor,
+    },
+    Deserialize, Deserializer,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub struct EditTimestamp(Option<u64>);
@@ -8,22 +17,44 @@ impl<'de> Deserialize<'de> for EditTimestamp {
     where
         D: Deserializer<'de>,
     {
-        let value: serde_json::Value = Deserialize::deserialize(deserializer)?;
-        match value {
-            serde_json::Value::Number(num) => {
-                dbg!(&num);
-                if let Some(num) = num.as_u64() {
-                    Ok(EditTimestamp(Some(num)))
+        struct EditTimestampVisitor;
+
+        impl<'de> Visitor<'de> for EditTimestampVisitor {
+            type Value = EditTimestamp;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a timestamp or false")
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(EditTimestamp(Some(v)))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                u64::try_from(v)
+                    .map(|v| EditTimestamp(Some(v)))
+                    .map_err(|_| E::custom("invalid number format"))
+            }
+
+            fn visit_f64<E: de::Error>(self, _: f64) -> Result<Self::Value, E> {
+                Err(E::custom("invalid number format"))
+            }
+
+            fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+                if v {
+                    Err(E::custom("invalid value for EditTimestamp"))
                 } else {
-                    Err(serde::de::Error::custom("invalid number format"))
+                    Ok(EditTimestamp(None))
                 }
             }
-            serde_json::Value::Bool(false) => Ok(EditTimestamp(None)),
-            _ => Err(serde::de::Error::custom("invalid value for EditTimestamp")),
         }
+
+        deserializer.deserialize_any(EditTimestampVisitor)
     }
 }
 
+/// Only lives while a response is taken apart, so its size doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, Clone)]
 pub enum ApiData {
     Single(RedditData),
@@ -35,23 +66,25 @@ impl<'de> Deserialize<'de> for ApiData {
     where
         D: Deserializer<'de>,
     {
-        let value: serde_json::Value = Deserialize::deserialize(deserializer)?;
-
-        match &value {
-            serde_json::Value::Array(_) => {
-                let reddit_data: Vec<RedditData> = serde_json::from_value(value)
-                    .map_err(|e| serde::de::Error::custom(e.to_string()))?;
-                Ok(ApiData::Collection(reddit_data))
-            }
-            serde_json::Value::Object(_) => {
-                let reddit_data: RedditData = serde_json::from_value(value)
-                    .map_err(|e| serde::de::Error::custom(e.to_string()))?;
-                Ok(ApiData::Single(reddit_data))
-            }
-            _ => Err(serde::de::Error::custom(
-                "invalid JSON structure for ApiData",
-            )),
+        struct ApiDataVisitor;
+
+        impl<'de> Visitor<'de> for ApiDataVisitor {
+            type Value = ApiData;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a Reddit object or an array of them")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                Deserialize::deserialize(SeqAccessDeserializer::new(seq)).map(ApiData::Collection)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                Deserialize::deserialize(MapAccessDeserializer::new(map)).map(ApiData::Single)
+            }
         }
+
+        deserializer.deserialize_any(ApiDataVisitor)
     }
 }
 
@@ -64,6 +97,15 @@ pub enum RedditData {
     WikiPage(WikiPageData),
     /// Post listing, Post
     T3(T3Data),
+    /// Account
+    T2(T2Data),
+    /// Subreddit
+    T5(T5Data),
+    /// Names of all the pages of a wiki
+    WikiPageListing(WikiPageListingData),
+    TrophyList(TrophyListData),
+    /// Trophy
+    T6(T6Data),
     /// Anything else, to be discarded
     Unknown(String),
 }
@@ -76,43 +118,87 @@ pub struct ListingData {
     pub before: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct InnerData {
-    kind: String,
-    data: serde_json::Value, // Using serde_json::Value to capture the inner object
-}
-
 impl<'de> Deserialize<'de> for RedditData {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let inner_data: InnerData = Deserialize::deserialize(deserializer)?;
+        deserializer.deserialize_map(RedditDataVisitor)
+    }
+}
 
-        match inner_data.kind.as_str() {
-            "Listing" => {
-                let listing_data: ListingData = serde_json::from_value(inner_data.data)
-                    .map_err(|e| serde::de::Error::custom(e.to_string()))?;
-                Ok(RedditData::Listing(listing_data))
-            }
-            "t1" => {
-                let t1_data: T1Data = serde_json::from_value(inner_data.data)
-                    .map_err(|e| serde::de::Error::custom(e.to_string()))?;
-                Ok(RedditData::T1(t1_data))
-            }
-            "t3" => {
-                let t3_data: T3Data = serde_json::from_value(inner_data.data)
-                    .map_err(|e| serde::de::Error::custom(e.to_string()))?;
-                Ok(RedditData::T3(t3_data))
+/// `{"kind": ..., "data": ...}`, decoded in one pass when the kind comes first, as it does from Reddit.
+struct RedditDataVisitor;
+
+impl<'de> Visitor<'de> for RedditDataVisitor {
+    type Value = RedditData;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a Reddit object with a kind and data")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut kind: Option<String> = None;
+        let mut data = None;
+        // data that came before its kind waits here
+        let mut pending: Option<serde_json::Value> = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "kind" => kind = Some(map.next_value()?),
+                "data" => match &kind {
+                    Some(k) => data = Some(map.next_value_seed(KindSeed(k))?),
+                    None => pending = Some(map.next_value()?),
+                },
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
             }
-            "wikipage" => {
-                let wiki_data: WikiPageData = serde_json::from_value(inner_data.data)
-                    .map_err(|e| serde::de::Error::custom(e.to_string()))?;
-                Ok(RedditData::WikiPage(wiki_data))
+        }
+
+        let kind = kind.ok_or_else(|| de::Error::missing_field("kind"))?;
+
+        match (data, pending) {
+            (Some(data), _) => Ok(data),
+            (None, Some(pending)) => KindSeed(&kind)
+                .deserialize(pending)
+                .map_err(de::Error::custom),
+            (None, None) => Err(de::Error::missing_field("data")),
+        }
+    }
+}
+
+/// The `data` of a Reddit object, whose type depends on its `kind`.
+struct KindSeed<'a>(&'a str);
+
+impl<'de> DeserializeSeed<'de> for KindSeed<'_> {
+    type Value = RedditData;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let data = match self.0 {
+            "Listing" => RedditData::Listing(Deserialize::deserialize(deserializer)?),
+            "t1" => RedditData::T1(Deserialize::deserialize(deserializer)?),
+            "t3" => RedditData::T3(Deserialize::deserialize(deserializer)?),
+            "wikipage" => RedditData::WikiPage(Deserialize::deserialize(deserializer)?),
+            "t2" => RedditData::T2(Deserialize::deserialize(deserializer)?),
+            "t5" => RedditData::T5(Deserialize::deserialize(deserializer)?),
+            "wikipagelisting" => {
+                RedditData::WikiPageListing(Deserialize::deserialize(deserializer)?)
             }
+            "TrophyList" => RedditData::TrophyList(Deserialize::deserialize(deserializer)?),
+            "t6" => RedditData::T6(Deserialize::deserialize(deserializer)?),
             // Handle other variants as needed
-            _ => Ok(RedditData::Unknown(inner_data.kind)),
-        }
+            kind => {
+                IgnoredAny::deserialize(deserializer)?;
+                crate::schema::unknown_kind(kind);
+                RedditData::Unknown(kind.to_string())
+            }
+        };
+
+        Ok(data)
     }
 }
 
@@ -120,62 +206,156 @@ impl<'de> Deserialize<'de> for RedditData {
 pub struct WikiPageData {
     pub content_md: String,
     pub revision_date: u64,
+    /// Boxed, as it's a `RedditData` itself
+    pub revision_by: Option<Box<RedditData>>,
 }
 
-/// Comment
 #[derive(Debug, Clone, Deserialize)]
-pub struct T1Data {
-    pub id: String,
-    pub subreddit: String,
-    pub body: String,
-    pub score: i32,
-    pub author: String,
-    //pub edited: EditTimestamp,
-    pub locked: bool,
-    pub stickied: bool,
-    //pub created_utc: u64,
-    pub replies: ReplyList,
-    pub author_flair_text: Option<String>,
-    pub author_flair_background_color: Option<String>,
-    pub is_submitter: bool,
-}
-
-/// Post listing, post
-#[derive(Debug, Clone, Deserialize)]
-pub struct T3Data {
+pub struct WikiPageListingData(pub Vec<String>);
+
+/// The revisions of a wiki are a `Listing` whose children have no `kind`,
+/// so they can't be deserialized as `RedditData`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WikiRevisionListingResponse {
+    pub data: WikiRevisionListing,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WikiRevisionListing {
+    pub after: Option<String>,
+    pub children: Vec<WikiRevision>,
+    pub before: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WikiRevision {
     pub id: String,
-    pub subreddit: String,
-    pub selftext: String,
+    pub page: String,
+    pub timestamp: f64,
+    pub reason: Option<String>,
+    pub author: Option<RedditData>,
+    #[serde(default)]
+    pub revision_hidden: bool,
+}
+
+/// Account
+#[derive(Debug, Clone, Deserialize)]
+pub struct T2Data {
+    pub name: String,
+    /// Suspended accounts only have a name
+    #[serde(default)]
+    pub is_suspended: bool,
+    pub link_karma: Option<i64>,
+    pub comment_karma: Option<i64>,
+    pub created_utc: Option<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrophyListData {
+    pub trophies: Vec<RedditData>,
+}
+
+/// Trophy
+#[derive(Debug, Clone, Deserialize)]
+pub struct T6Data {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/// Subreddit
+#[derive(Debug, Clone, Deserialize)]
+pub struct T5Data {
     pub title: String,
-    pub score: i32,
-    pub author: String,
-    //pub edited: EditTimestamp,
-    pub locked: bool,
-    pub stickied: bool,
-    pub spoiler: bool,
-    //pub created_utc: u64,
-    pub thumbnail: Option<String>,
-    pub upvote_ratio: f32,
-    pub archived: bool,
-    pub pinned: bool,
-    pub over_18: bool,
-    pub author_flair_text: Option<String>,
-    pub author_flair_background_color: Option<String>,
-    pub num_comments: u32,
-    pub subreddit_subscribers: u32,
-    pub is_video: bool,
-    pub is_gallery: Option<bool>,
-    pub is_reddit_media_domain: bool,
-    pub link_flair_text: Option<String>,
-    pub link_flair_background_color: Option<String>,
-    pub url: Option<String>,
-    pub gallery_data: Option<GalleryData>,
-    // media_metadata
-    pub poll_data: Option<PollData>
+    pub public_description: String,
+    /// Sidebar
+    pub description: String,
+    pub subscribers: Option<u64>,
+    pub active_user_count: Option<u64>,
+    pub created_utc: f64,
+    pub over18: Option<bool>,
+    pub quarantine: bool,
+    pub subreddit_type: String,
 }
 
-#[derive(Debug, Clone)]
+/// Rules aren't wrapped in a `kind` either.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubredditRulesResponse {
+    pub rules: Vec<SubredditRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubredditRule {
+    pub short_name: String,
+    pub description: String,
+}
+
+crate::schema::lenient! {
+    /// Comment
+    #[derive(Debug, Clone)]
+    pub struct T1Data as "t1" {
+        pub id: String,
+        pub subreddit: String,
+        pub body: String,
+        pub score: i32,
+        pub author: String,
+        //pub edited: EditTimestamp,
+        pub locked: bool,
+        pub stickied: bool,
+        pub created_utc: f64,
+        pub replies: ReplyList,
+        pub author_flair_text: Option<String>,
+        pub author_flair_background_color: Option<String>,
+        pub is_submitter: bool,
+        /// Fullname of the post
+        pub link_id: String,
+        /// Only in user listings
+        pub link_title: Option<String>,
+        pub permalink: String,
+    }
+}
+
+crate::schema::lenient! {
+    /// Post listing, post
+    #[derive(Debug, Clone)]
+    pub struct T3Data as "t3" {
+        pub id: String,
+        pub subreddit: String,
+        pub selftext: String,
+        pub title: String,
+        pub score: i32,
+        pub author: String,
+        /// `self.<subreddit>` for text posts
+        pub domain: String,
+        //pub edited: EditTimestamp,
+        pub locked: bool,
+        pub stickied: bool,
+        pub spoiler: bool,
+        pub created_utc: f64,
+        pub thumbnail: Option<String>,
+        pub upvote_ratio: f32,
+        pub archived: bool,
+        pub pinned: bool,
+        pub over_18: bool,
+        pub author_flair_text: Option<String>,
+        pub author_flair_background_color: Option<String>,
+        pub num_comments: u32,
+        pub subreddit_subscribers: u32,
+        pub is_video: bool,
+        pub is_gallery: Option<bool>,
+        pub is_reddit_media_domain: bool,
+        pub link_flair_text: Option<String>,
+        pub link_flair_background_color: Option<String>,
+        pub url: Option<String>,
+        pub gallery_data: Option<GalleryData>,
+        /// Of gallery items, by media id
+        pub media_metadata: Option<HashMap<String, MediaMetadata>>,
+        pub poll_data: Option<PollData>,
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub enum ReplyList {
+    #[default]
     None,
     Replies(ListingData),
 }
@@ -185,14 +365,307 @@ impl<'de> Deserialize<'de> for ReplyList {
     where
         D: Deserializer<'de>,
     {
-        let value: InnerData = match Deserialize::deserialize(deserializer) {
-            Ok(v) => v,
-            Err(_) => return Ok(ReplyList::None),
+        Ok(PartSeed(Replies)
+            .deserialize(deserializer)?
+            .unwrap_or_default())
+    }
+}
+
+/// Levels of replies decoded, deeper ones are skipped.
+/// Each level takes five of the 128 levels serde_json nests to, and Reddit cuts threads at ten anyway.
+const MAX_REPLY_DEPTH: usize = 20;
+
+thread_local! {
+    static REPLY_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Replies are decoded in place, as deep as the thread goes:
+/// a part that isn't shaped like replies is skipped, so only its own subtree is lost.
+impl crate::schema::Field for ReplyList {
+    fn decode<'de, A: MapAccess<'de>>(
+        map: &mut A,
+        kind: &'static str,
+        name: &'static str,
+    ) -> Result<Self, A::Error> {
+        let depth = REPLY_DEPTH.with(Cell::get);
+        if depth >= MAX_REPLY_DEPTH {
+            // skipping doesn't recurse, however deep the rest is
+            map.next_value::<IgnoredAny>()?;
+            crate::schema::failure(kind, name, &"replies nested too deep");
+            return Ok(ReplyList::None);
+        }
+
+        REPLY_DEPTH.with(|d| d.set(depth + 1));
+        // JSON that doesn't parse still fails the whole response
+        let replies = map.next_value_seed(PartSeed(Replies));
+        REPLY_DEPTH.with(|d| d.set(depth));
+
+        match replies? {
+            Ok(replies) => Ok(replies),
+            Err(expected) => {
+                crate::schema::failure(kind, name, &format_args!("expected {}", expected));
+                Ok(ReplyList::None)
+            }
+        }
+    }
+}
+
+/// A part of the replies, or what it was expected to be if it had another shape.
+/// The part has been read either way, so decoding carries on after it.
+type Shape<T> = Result<T, &'static str>;
+
+/// One part of the replies. Anything it doesn't take is skipped whole.
+trait Part<'de>: Sized {
+    type Value;
+
+    const EXPECTED: &'static str;
+
+    fn map<A: MapAccess<'de>>(self, mut map: A) -> Result<Shape<Self::Value>, A::Error> {
+        while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+        Ok(Err(Self::EXPECTED))
+    }
+
+    fn seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Shape<Self::Value>, A::Error> {
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(Err(Self::EXPECTED))
+    }
+
+    fn str(self, _: &str) -> Shape<Self::Value> {
+        self.scalar()
+    }
+
+    /// Numbers, booleans and null
+    fn scalar(self) -> Shape<Self::Value> {
+        Err(Self::EXPECTED)
+    }
+}
+
+struct PartSeed<P>(P);
+
+impl<'de, P: Part<'de>> DeserializeSeed<'de> for PartSeed<P> {
+    type Value = Shape<P::Value>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, P: Part<'de>> Visitor<'de> for PartSeed<P> {
+    type Value = Shape<P::Value>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(P::EXPECTED)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        self.0.map(map)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        self.0.seq(seq)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(self.0.str(v))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        Ok(self.0.scalar())
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<Self::Value, E> {
+        Ok(self.0.scalar())
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<Self::Value, E> {
+        Ok(self.0.scalar())
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<Self::Value, E> {
+        Ok(self.0.scalar())
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<Self::Value, E> {
+        Ok(self.0.scalar())
+    }
+}
+
+/// Reddit sends `""` when there are no replies, anything but a listing is taken as that.
+struct Replies;
+
+impl<'de> Part<'de> for Replies {
+    type Value = ReplyList;
+
+    const EXPECTED: &'static str = "a listing of replies";
+
+    fn map<A: MapAccess<'de>>(self, mut map: A) -> Result<Shape<ReplyList>, A::Error> {
+        let mut kind = None;
+        let mut listing = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "kind" => kind = Some(map.next_value_seed(PartSeed(Kind))?),
+                // replies are always listings, so their data doesn't wait for the kind
+                "data" => listing = Some(map.next_value_seed(PartSeed(Listing))?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(match kind {
+            Some(Ok(kind)) if kind == "Listing" => match listing {
+                Some(listing) => listing.map(ReplyList::Replies),
+                None => Err(Self::EXPECTED),
+            },
+            Some(Ok(_)) => Ok(ReplyList::None),
+            _ => Err(Self::EXPECTED),
+        })
+    }
+
+    fn seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Shape<ReplyList>, A::Error> {
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(Ok(ReplyList::None))
+    }
+
+    fn scalar(self) -> Shape<ReplyList> {
+        Ok(ReplyList::None)
+    }
+}
+
+struct Kind;
+
+impl<'de> Part<'de> for Kind {
+    type Value = String;
+
+    const EXPECTED: &'static str = "a kind";
+
+    fn str(self, v: &str) -> Shape<String> {
+        Ok(v.to_string())
+    }
+}
+
+struct Listing;
+
+impl<'de> Part<'de> for Listing {
+    type Value = ListingData;
+
+    const EXPECTED: &'static str = "a listing";
+
+    fn map<A: MapAccess<'de>>(self, mut map: A) -> Result<Shape<ListingData>, A::Error> {
+        use crate::schema::Field;
+
+        let mut listing = ListingData {
+            dist: None,
+            after: None,
+            children: Vec::new(),
+            before: None,
         };
+        let mut children = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "dist" => listing.dist = Field::decode(&mut map, "Listing", "dist")?,
+                "after" => listing.after = Field::decode(&mut map, "Listing", "after")?,
+                "before" => listing.before = Field::decode(&mut map, "Listing", "before")?,
+                "children" => children = Some(map.next_value_seed(PartSeed(Children))?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(match children {
+            Some(children) => children.map(|children| ListingData {
+                children,
+                ..listing
+            }),
+            None => Err(Self::EXPECTED),
+        })
+    }
+}
+
+struct Children;
+
+impl<'de> Part<'de> for Children {
+    type Value = Vec<RedditData>;
+
+    const EXPECTED: &'static str = "a list of replies";
+
+    fn seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Shape<Vec<RedditData>>, A::Error> {
+        let mut children = Vec::new();
+
+        while let Some(child) = seq.next_element_seed(PartSeed(Child))? {
+            match child {
+                Ok(child) => children.push(child),
+                Err(expected) => {
+                    while seq.next_element::<IgnoredAny>()?.is_some() {}
+                    return Ok(Err(expected));
+                }
+            }
+        }
 
-        Ok(ReplyList::Replies(
-            serde_json::from_value(value.data).map_err(|e| serde::de::Error::custom(e))?,
-        ))
+        Ok(Ok(children))
+    }
+}
+
+/// A comment, or the link to more of them.
+struct Child;
+
+impl<'de> Part<'de> for Child {
+    type Value = RedditData;
+
+    const EXPECTED: &'static str = "a comment";
+
+    fn map<A: MapAccess<'de>>(self, mut map: A) -> Result<Shape<RedditData>, A::Error> {
+        let mut kind = None;
+        let mut data = None;
+        // data that came before its kind waits here
+        let mut pending: Option<serde_json::Value> = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match (key.as_str(), &kind) {
+                ("kind", _) => kind = Some(map.next_value_seed(PartSeed(Kind))?),
+                ("data", Some(Ok(kind))) => data = Some(map.next_value_seed(ChildData(kind))?),
+                ("data", _) => pending = Some(map.next_value()?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(match (kind, data, pending) {
+            (Some(Ok(_)), Some(data), _) => Ok(data),
+            (Some(Ok(kind)), None, Some(pending)) => ChildData(&kind)
+                .deserialize(pending)
+                .map_err(|_| Self::EXPECTED),
+            _ => Err(Self::EXPECTED),
+        })
+    }
+}
+
+/// The `data` of a reply: comments are decoded, the rest is skipped.
+struct ChildData<'a>(&'a str);
+
+impl<'de> DeserializeSeed<'de> for ChildData<'_> {
+    type Value = RedditData;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match self.0 {
+            "t1" => Deserialize::deserialize(deserializer).map(RedditData::T1),
+            // the link to the rest of a long thread, among others
+            kind => {
+                IgnoredAny::deserialize(deserializer)?;
+                crate::schema::unknown_kind(kind);
+                Ok(RedditData::Unknown(kind.to_string()))
+            }
+        }
     }
 }
 
@@ -209,6 +682,10 @@ pub struct PollOption {
     pub id: String
 }
 
+impl crate::schema::Field for Option<GalleryData> {}
+impl crate::schema::Field for Option<PollData> {}
+impl crate::schema::Field for Option<HashMap<String, MediaMetadata>> {}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct GalleryData {
     pub items: Vec<GalleryItem>,
@@ -217,4 +694,10 @@ pub struct GalleryData {
 #[derive(Debug, Clone, Deserialize)]
 pub struct GalleryItem {
     pub media_id: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MediaMetadata {
+    /// Media type, like `image/png`
+    pub m: Option<String>,
 }
\ No newline at end of file
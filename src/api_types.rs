@@ -1,6 +1,6 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub enum SortingMode {
     #[default]
     #[serde(rename = "suggested")]
@@ -17,7 +17,7 @@ pub enum SortingMode {
     Top,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub enum TopSortingTime {
     #[serde(rename = "hour")]
     PastHour,
@@ -34,7 +34,7 @@ pub enum TopSortingTime {
     AllTime,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub enum CommentSortingMode {
     #[default]
     #[serde(rename = "suggested")]
@@ -53,7 +53,7 @@ pub enum CommentSortingMode {
     QAndA,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub enum SearchSortingMode {
     #[default]
     #[serde(rename = "relevance")]
@@ -64,7 +64,7 @@ pub enum SearchSortingMode {
     Comments,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub enum SearchTimeOrdering {
     #[serde(rename = "hour")]
     PastHour,
@@ -81,7 +81,7 @@ pub enum SearchTimeOrdering {
     AllTime,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub enum UserSortingMode {
     #[serde(rename = "hot")]
     Hot,
@@ -94,7 +94,7 @@ pub enum UserSortingMode {
     Top,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub enum UserFilterMode {
     #[default]
     #[serde(rename = "overview")]
@@ -0,0 +1,477 @@
+use axum::{
+    extract::{Path, Query, State},
+    headers::UserAgent,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json, TypedHeader,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{CommentsQuery, PostType, SubredditQuery, UserQuery},
+    api_result_types::{RedditData, ReplyList, T1Data, T3Data, WikiPageData},
+    api_types::{
+        CommentSortingMode, SearchTimeOrdering, SortingMode, TopSortingTime, UserFilterMode,
+        UserSortingMode,
+    },
+    filters::{Blocklist, ContentFilter},
+    nsfw::{NsfwPolicy, NsfwSubreddits},
+    pagination::{PageRequest, Pagination},
+    preferences::Preferences,
+    subscriptions::Subscriptions,
+    upstream::Client,
+};
+
+/// Where the JSON API for third-party clients is nested.
+/// Responses have their own types instead of Reddit's, so they only change with the version.
+/// Listings are paged with opaque tokens: `next` and `previous` go back in as `?page=`.
+pub const PREFIX: &str = "/api/v1";
+
+/// An error, as `{"error": "Not Found"}` with the matching status.
+#[derive(Debug)]
+pub struct Error(StatusCode);
+
+impl From<StatusCode> for Error {
+    fn from(status: StatusCode) -> Self {
+        Error(status)
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let error = self.0.canonical_reason().unwrap_or("Error");
+        (self.0, Json(serde_json::json!({ "error": error }))).into_response()
+    }
+}
+
+/// A page of a listing.
+#[derive(Debug, Serialize)]
+pub struct Listing<T> {
+    pub items: Vec<T>,
+    /// Items removed by the server's filters
+    pub hidden: usize,
+    /// Token of the following page
+    pub next: Option<String>,
+    /// Token of the page before
+    pub previous: Option<String>,
+}
+
+impl<T> Listing<T> {
+    fn new(items: Vec<T>, hidden: usize, pagination: &Pagination) -> Listing<T> {
+        Listing {
+            items,
+            hidden,
+            next: pagination.next_page().map(|p| p.token()),
+            previous: pagination.previous_page().map(|p| p.token()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Post {
+    pub id: String,
+    pub subreddit: String,
+    pub title: String,
+    pub author: String,
+    pub kind: PostType,
+    pub score: i32,
+    pub num_comments: u32,
+    /// Unix timestamp, in seconds
+    pub created: i64,
+    /// Where link and image posts point
+    pub url: Option<String>,
+    pub domain: String,
+    /// Markdown, empty for anything but text posts
+    pub body: String,
+    /// Gallery images, in order
+    pub images: Vec<String>,
+    pub flair: Option<String>,
+    pub nsfw: bool,
+    pub spoiler: bool,
+    pub stickied: bool,
+    pub locked: bool,
+}
+
+impl From<&T3Data> for Post {
+    fn from(post: &T3Data) -> Self {
+        let images = post
+            .gallery_data
+            .iter()
+            .flat_map(|g| &g.items)
+            .map(|i| crate::image_proxy::reddit_image_url(&post.gallery_file(i)))
+            .collect();
+
+        Post {
+            id: post.id.clone(),
+            subreddit: post.subreddit.clone(),
+            title: post.title.clone(),
+            author: post.author.clone(),
+            kind: post.post_type(),
+            score: post.score,
+            num_comments: post.num_comments,
+            created: post.created_utc as i64,
+            url: post.url.clone(),
+            domain: post.domain.clone(),
+            body: post.selftext.clone(),
+            images,
+            flair: post.link_flair_text.clone(),
+            nsfw: post.over_18,
+            spoiler: post.spoiler,
+            stickied: post.stickied,
+            locked: post.locked,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Comment {
+    pub id: String,
+    pub author: String,
+    /// Markdown
+    pub body: String,
+    pub score: i32,
+    /// Unix timestamp, in seconds
+    pub created: i64,
+    pub flair: Option<String>,
+    /// Whether the author wrote the post
+    pub is_op: bool,
+    pub stickied: bool,
+    pub locked: bool,
+    pub replies: Vec<Comment>,
+}
+
+impl From<&T1Data> for Comment {
+    fn from(comment: &T1Data) -> Self {
+        let replies = match &comment.replies {
+            ReplyList::Replies(list) => list
+                .children
+                .iter()
+                .filter_map(|c| match c {
+                    RedditData::T1(reply) => Some(Comment::from(reply)),
+                    _ => None,
+                })
+                .collect(),
+            ReplyList::None => Vec::new(),
+        };
+
+        Comment {
+            id: comment.id.clone(),
+            author: comment.author.clone(),
+            body: comment.body.clone(),
+            score: comment.score,
+            created: comment.created_utc as i64,
+            flair: comment.author_flair_text.clone(),
+            is_op: comment.is_submitter,
+            stickied: comment.stickied,
+            locked: comment.locked,
+            replies,
+        }
+    }
+}
+
+/// A post and its comment tree.
+#[derive(Debug, Serialize)]
+pub struct Thread {
+    pub post: Post,
+    pub comments: Vec<Comment>,
+}
+
+impl From<&CommentsQuery> for Thread {
+    fn from(data: &CommentsQuery) -> Self {
+        Thread {
+            post: Post::from(&data.post),
+            comments: data.comments.iter().map(Comment::from).collect(),
+        }
+    }
+}
+
+/// What users post, told apart by `type`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum UserItem {
+    Post(Post),
+    Comment(UserComment),
+}
+
+/// A comment out of its thread.
+#[derive(Debug, Serialize)]
+pub struct UserComment {
+    pub id: String,
+    pub subreddit: String,
+    /// Id of the post it answers
+    pub post_id: String,
+    pub post_title: Option<String>,
+    /// Markdown
+    pub body: String,
+    pub score: i32,
+    /// Unix timestamp, in seconds
+    pub created: i64,
+}
+
+impl From<&T1Data> for UserComment {
+    fn from(comment: &T1Data) -> Self {
+        UserComment {
+            id: comment.id.clone(),
+            subreddit: comment.subreddit.clone(),
+            post_id: comment.link_id.trim_start_matches("t3_").to_string(),
+            post_title: comment.link_title.clone(),
+            body: comment.body.clone(),
+            score: comment.score,
+            created: comment.created_utc as i64,
+        }
+    }
+}
+
+impl From<&UserQuery> for Listing<UserItem> {
+    fn from(data: &UserQuery) -> Self {
+        let items = data
+            .children
+            .iter()
+            .filter_map(|c| match c {
+                RedditData::T3(post) => Some(UserItem::Post(Post::from(post))),
+                RedditData::T1(comment) => Some(UserItem::Comment(UserComment::from(comment))),
+                _ => None,
+            })
+            .collect();
+
+        Listing::new(items, data.hidden, &data.pagination)
+    }
+}
+
+impl From<&SubredditQuery> for Listing<Post> {
+    fn from(data: &SubredditQuery) -> Self {
+        let items = data.posts.iter().map(Post::from).collect();
+        Listing::new(items, data.hidden, &data.pagination)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WikiPage {
+    pub subreddit: String,
+    pub page: String,
+    /// Markdown
+    pub body: String,
+    /// Unix timestamp of the revision, in seconds
+    pub revised: u64,
+    pub revised_by: Option<String>,
+}
+
+impl WikiPage {
+    fn new(subreddit: String, page: String, data: WikiPageData) -> WikiPage {
+        let revised_by = match data.revision_by.as_deref() {
+            Some(RedditData::T2(t2)) => Some(t2.name.clone()),
+            _ => None,
+        };
+
+        WikiPage {
+            subreddit,
+            page,
+            body: data.content_md,
+            revised: data.revision_date,
+            revised_by,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WikiPages {
+    pub subreddit: String,
+    pub pages: Vec<String>,
+}
+
+/// `?page=` of listings, a token they returned.
+#[derive(Debug, Deserialize)]
+pub struct PageParams {
+    page: Option<String>,
+}
+
+impl PageParams {
+    fn request(&self) -> Result<PageRequest, Error> {
+        match &self.page {
+            Some(token) => PageRequest::from_token(token).ok_or(Error(StatusCode::BAD_REQUEST)),
+            None => Ok(PageRequest::default()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubredditParams {
+    sort: Option<SortingMode>,
+    t: Option<TopSortingTime>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn subreddit(
+    Path(subreddit): Path<String>,
+    Query(params): Query<SubredditParams>,
+    Query(page): Query<PageParams>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+    State(blocklist): State<Blocklist>,
+    State(policy): State<NsfwPolicy>,
+    State(nsfw_subreddits): State<NsfwSubreddits>,
+) -> Result<Json<Listing<Post>>, Error> {
+    let user_agent = user_agent.as_str();
+
+    if policy.disabled
+        && Subscriptions::can_subscribe(&subreddit)
+        && nsfw_subreddits
+            .is_nsfw(&client, &subreddit, user_agent)
+            .await
+    {
+        return Err(Error(StatusCode::FORBIDDEN));
+    }
+
+    let prefs = Preferences::default();
+    let fetch = |page: PageRequest| {
+        let (client, subreddit, prefs) = (&client, &subreddit, &prefs);
+        async move {
+            crate::api::subreddit(
+                client,
+                subreddit,
+                params.sort,
+                params.t,
+                &page,
+                prefs,
+                user_agent,
+            )
+            .await
+        }
+    };
+
+    // only the server's filters, clients get the same data whatever cookies they send
+    let mut data = fetch(page.request()?).await?;
+    ContentFilter::server(blocklist, policy)
+        .apply(&mut data, fetch)
+        .await;
+
+    Ok(Json(Listing::from(&data)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentsParams {
+    sort: Option<CommentSortingMode>,
+}
+
+pub async fn comments(
+    Path((subreddit, id)): Path<(String, String)>,
+    Query(params): Query<CommentsParams>,
+    Query(page): Query<PageParams>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+    State(policy): State<NsfwPolicy>,
+) -> Result<Json<Thread>, Error> {
+    let data = crate::api::comments(
+        &client,
+        &subreddit,
+        &id,
+        None,
+        params.sort,
+        &page.request()?,
+        &Preferences::default(),
+        user_agent.as_str(),
+    )
+    .await?;
+
+    if data.post.over_18 && policy.disabled {
+        return Err(Error(StatusCode::FORBIDDEN));
+    }
+
+    Ok(Json(Thread::from(&data)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserParams {
+    sort: Option<UserSortingMode>,
+    filter: Option<UserFilterMode>,
+    t: Option<SearchTimeOrdering>,
+}
+
+pub async fn user(
+    Path(username): Path<String>,
+    Query(params): Query<UserParams>,
+    Query(page): Query<PageParams>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+    State(blocklist): State<Blocklist>,
+    State(policy): State<NsfwPolicy>,
+) -> Result<Json<Listing<UserItem>>, Error> {
+    let fetch = |page: PageRequest| {
+        let (client, username, user_agent) = (&client, &username, user_agent.as_str());
+        async move {
+            crate::api::user(
+                client,
+                username,
+                params.sort,
+                params.t,
+                params.filter,
+                &page,
+                user_agent,
+            )
+            .await
+        }
+    };
+
+    // only the server's filters, clients get the same data whatever cookies they send
+    let mut data = fetch(page.request()?).await?;
+    ContentFilter::server(blocklist, policy)
+        .apply(&mut data, fetch)
+        .await;
+
+    Ok(Json(Listing::from(&data)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WikiParams {
+    /// Revision id
+    v: Option<String>,
+}
+
+pub async fn wiki_index(
+    Path(subreddit): Path<String>,
+    Query(params): Query<WikiParams>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+) -> Result<Response, Error> {
+    wiki(&client, subreddit, "index", params, user_agent.as_str()).await
+}
+
+/// A page, or the list of pages for `pages`, like the HTML route.
+pub async fn wiki_page(
+    Path((subreddit, path)): Path<(String, String)>,
+    Query(params): Query<WikiParams>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+) -> Result<Response, Error> {
+    let path = path.trim_matches('/');
+    wiki(&client, subreddit, path, params, user_agent.as_str()).await
+}
+
+async fn wiki(
+    client: &Client,
+    subreddit: String,
+    path: &str,
+    params: WikiParams,
+    user_agent: &str,
+) -> Result<Response, Error> {
+    if path == "pages" {
+        let data = crate::api::wiki_pages(client, &subreddit, user_agent).await?;
+
+        return Ok(Json(WikiPages {
+            subreddit,
+            pages: data.0,
+        })
+        .into_response());
+    }
+
+    let data = crate::api::wiki(
+        client,
+        &subreddit,
+        Some(path),
+        params.v.as_deref(),
+        user_agent,
+    )
+    .await?;
+
+    Ok(Json(WikiPage::new(subreddit, path.to_string(), data)).into_response())
+}
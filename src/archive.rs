@@ -0,0 +1,696 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context};
+use askama::Template;
+use axum::http::Uri;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{CommentsQuery, SubredditQuery},
+    comments::CommentsTemplate,
+    filters::{Blocklist, ContentFilter, FilterRules},
+    nsfw::NsfwPolicy,
+    pagination::PageRequest,
+    preferences::Preferences,
+    share::ShareLinks,
+    subreddit::SubredditTemplate,
+    themes::{CurrentTheme, Themes},
+    upstream::Client,
+};
+
+/// Reddit gets ours, like from the other frontends.
+const USER_AGENT: &str = concat!("older-reddit/", env!("CARGO_PKG_VERSION"), " (archive)");
+
+/// What was saved and when, kept next to the pages so runs can resume and update.
+const MANIFEST_FILE: &str = "archive.json";
+
+/// Where links to pages that weren't archived go.
+const REDDIT: &str = "https://www.reddit.com";
+
+/// What to archive, as given on the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// `r/<name>`, its first pages and their threads
+    Subreddit(String),
+    /// A thread id, or `r/<subreddit>/comments/<id>`
+    Thread {
+        subreddit: Option<String>,
+        id: String,
+    },
+}
+
+impl Target {
+    pub fn parse(target: &str) -> Option<Target> {
+        let segments: Vec<&str> = target.split('/').filter(|s| !s.is_empty()).collect();
+
+        let target = match segments.as_slice() {
+            ["r", subreddit] => Target::Subreddit(subreddit.to_string()),
+            ["r", subreddit, "comments", id, ..] => Target::Thread {
+                subreddit: Some(subreddit.to_string()),
+                id: id.to_string(),
+            },
+            [id] => Target::Thread {
+                subreddit: None,
+                id: id.to_string(),
+            },
+            _ => return None,
+        };
+
+        let valid = |s: &str| s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        match &target {
+            Target::Subreddit(name) if valid(name) => Some(target),
+            Target::Thread { subreddit, id }
+                if valid(id) && subreddit.as_deref().is_none_or(valid) =>
+            {
+                Some(target)
+            }
+            _ => None,
+        }
+    }
+}
+
+pub struct Options {
+    /// Directory the archive is written to, and read back from when resuming
+    pub out: PathBuf,
+    /// Listing pages saved for each subreddit
+    pub depth: usize,
+    /// Pause between requests to Reddit
+    pub delay: Duration,
+    /// Saves threads again even when they haven't changed
+    pub refresh: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// Number of listing pages saved, by lowercase name
+    subreddits: BTreeMap<String, usize>,
+    /// By id
+    threads: BTreeMap<String, SavedThread>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedThread {
+    subreddit: String,
+    title: String,
+    /// Compared with listings to tell whether it needs saving again
+    num_comments: u32,
+    /// Unix timestamp
+    saved: i64,
+}
+
+/// A listing page waiting to be written.
+struct ListingPage {
+    subreddit: String,
+    number: usize,
+    uri: Uri,
+    data: SubredditQuery,
+}
+
+/// Saves subreddits and threads as static HTML that can be opened without a server.
+pub struct Archiver {
+    client: Client,
+    share_links: ShareLinks,
+    filter: ContentFilter,
+    themes: Themes,
+    theme: CurrentTheme,
+    options: Options,
+    manifest: Manifest,
+    /// Local URLs that were archived, lowercase, to the file they were saved to
+    links: HashMap<String, String>,
+}
+
+impl Archiver {
+    /// Uses the server's blocklist, NSFW switch and themes, and what was archived before in `out`.
+    pub fn new(options: Options) -> anyhow::Result<Archiver> {
+        let manifest = match std::fs::read_to_string(options.out.join(MANIFEST_FILE)) {
+            Ok(json) => serde_json::from_str(&json).context("Unreadable archive manifest")?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Manifest::default(),
+            Err(e) => return Err(e.into()),
+        };
+
+        let blocklist = Blocklist(Arc::new(FilterRules::blocklist()?));
+        let themes = Themes::load()?;
+
+        // everything Reddit sent is kept, so the archive can be served with `--offline` too
+        let client = Client::record(&options.out);
+
+        Ok(Archiver {
+            share_links: ShareLinks::new(crate::utils::get_reddit_domain(), &client)?,
+            client,
+            filter: ContentFilter::server(blocklist, NsfwPolicy::from_env()),
+            theme: themes.current(None),
+            themes,
+            options,
+            manifest,
+            links: HashMap::new(),
+        })
+    }
+
+    pub async fn run(&mut self, targets: &[Target]) -> anyhow::Result<()> {
+        let mut listings = Vec::new();
+        // (subreddit, id), and the data when it was already fetched
+        let mut threads: Vec<(String, String, Option<CommentsQuery>)> = Vec::new();
+
+        for target in targets {
+            match target {
+                Target::Subreddit(name) => {
+                    let pages = self.crawl(name).await?;
+
+                    for post in pages.iter().flat_map(|p| &p.data.posts) {
+                        let queued = threads.iter().any(|(_, id, _)| *id == post.id);
+                        if !queued && self.needs_saving(&post.id, post.num_comments) {
+                            threads.push((post.subreddit.clone(), post.id.clone(), None));
+                        }
+                    }
+
+                    self.manifest
+                        .subreddits
+                        .insert(name.to_lowercase(), pages.len());
+                    listings.extend(pages);
+                }
+                Target::Thread { subreddit, id } => {
+                    // saved threads are fetched again too, to tell whether they have new comments
+                    let subreddit = subreddit
+                        .clone()
+                        .or_else(|| self.manifest.threads.get(id).map(|t| t.subreddit.clone()));
+
+                    // the post tells us which subreddit it's really in
+                    let data = self.fetch_thread(subreddit.as_deref(), id).await?;
+                    if !self.needs_saving(id, data.post.num_comments) {
+                        tracing::info!("Thread {} has no new comments", id);
+                        continue;
+                    }
+                    threads.push((data.post.subreddit.clone(), id.clone(), Some(data)));
+                }
+            }
+        }
+
+        // every page is known before any is written, so they can all link to each other
+        for (subreddit, id, _) in &threads {
+            self.links.insert(
+                format!("/r/{}/comments/{}", subreddit, id).to_lowercase(),
+                thread_file(subreddit, id),
+            );
+        }
+        self.link_saved_pages();
+        for page in &listings {
+            self.link_listing(page);
+        }
+
+        let total = threads.len();
+        for (i, (subreddit, id, data)) in threads.into_iter().enumerate() {
+            let data = match data {
+                Some(d) => d,
+                None => {
+                    tokio::time::sleep(self.options.delay).await;
+
+                    match self.fetch_thread(Some(&subreddit), &id).await {
+                        Ok(d) => d,
+                        // the rest is still worth saving, and the next run tries again
+                        Err(e) => {
+                            tracing::warn!("Skipping thread {}: {}", id, e);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            tracing::info!("Saving thread {} ({}/{})", id, i + 1, total);
+            self.save_thread(data).await?;
+        }
+
+        for page in listings {
+            tracing::info!("Saving r/{} page {}", page.subreddit, page.number);
+            self.save_listing(page).await?;
+        }
+
+        self.save_index().await?;
+        self.save_manifest()?;
+
+        tracing::info!(
+            "Archive saved, open {}",
+            self.options.out.join("index.html").display()
+        );
+        Ok(())
+    }
+
+    /// Whether a thread is new, or has comments we don't have.
+    fn needs_saving(&self, id: &str, num_comments: u32) -> bool {
+        let saved = self.manifest.threads.get(id);
+
+        self.options.refresh
+            || saved.is_none_or(|t| {
+                t.num_comments != num_comments
+                    || !self
+                        .options
+                        .out
+                        .join(thread_file(&t.subreddit, id))
+                        .exists()
+            })
+    }
+
+    /// The first `depth` pages of a subreddit, without what the server filters out.
+    async fn crawl(&self, subreddit: &str) -> anyhow::Result<Vec<ListingPage>> {
+        let prefs = Preferences::default();
+        let mut pages = Vec::new();
+        let mut page = PageRequest::default();
+        let mut uri: Uri = format!("/r/{}", subreddit).parse()?;
+
+        for number in 1..=self.options.depth {
+            if number > 1 {
+                tokio::time::sleep(self.options.delay).await;
+            }
+
+            tracing::info!("Fetching r/{} page {}", subreddit, number);
+
+            let fetch = |page: PageRequest| {
+                let prefs = &prefs;
+                async move {
+                    crate::api::subreddit(
+                        &self.client,
+                        subreddit,
+                        None,
+                        None,
+                        &page,
+                        prefs,
+                        USER_AGENT,
+                    )
+                    .await
+                }
+            };
+
+            let mut data = fetch(page)
+                .await
+                .map_err(|e| anyhow!("Couldn't fetch r/{}: {}", subreddit, e))?;
+            self.filter.apply(&mut data, fetch).await;
+
+            let next = data.pagination.next_page();
+            let next_uri = data.pagination.after_url(&uri);
+
+            pages.push(ListingPage {
+                subreddit: subreddit.to_string(),
+                number,
+                uri: uri.clone(),
+                data,
+            });
+
+            match (next, next_uri) {
+                (Some(n), Some(u)) => {
+                    page = n;
+                    uri = u.parse()?;
+                }
+                _ => break,
+            }
+        }
+
+        Ok(pages)
+    }
+
+    async fn fetch_thread(
+        &self,
+        subreddit: Option<&str>,
+        id: &str,
+    ) -> anyhow::Result<CommentsQuery> {
+        let subreddit = match subreddit {
+            Some(s) => s.to_string(),
+            None => {
+                let path = self
+                    .share_links
+                    .post_path(id, USER_AGENT)
+                    .await
+                    .map_err(|e| anyhow!("Couldn't find thread {}: {}", id, e))?;
+
+                // `/r/:subreddit/comments/:id`
+                path.split('/')
+                    .nth(2)
+                    .ok_or_else(|| anyhow!("Couldn't find thread {}", id))?
+                    .to_string()
+            }
+        };
+
+        crate::api::comments(
+            &self.client,
+            &subreddit,
+            id,
+            None,
+            None,
+            &PageRequest::default(),
+            &Preferences::default(),
+            USER_AGENT,
+        )
+        .await
+        .map_err(|e| anyhow!("Couldn't fetch thread {}: {}", id, e))
+    }
+
+    /// Pages archived by earlier runs, which this one can link to.
+    fn link_saved_pages(&mut self) {
+        for (subreddit, pages) in &self.manifest.subreddits {
+            if *pages > 0 {
+                self.links
+                    .insert(format!("/r/{}", subreddit), listing_file(subreddit, 1));
+            }
+        }
+
+        for (id, thread) in &self.manifest.threads {
+            self.links.insert(
+                format!("/r/{}/comments/{}", thread.subreddit, id).to_lowercase(),
+                thread_file(&thread.subreddit, id),
+            );
+        }
+    }
+
+    /// The URLs the templates use for a listing page, and for the pages around it.
+    fn link_listing(&mut self, page: &ListingPage) {
+        let file = listing_file(&page.subreddit, page.number);
+        self.links
+            .insert(page.uri.to_string().to_lowercase(), file.clone());
+
+        if page.number > 1 {
+            if let Some(before) = page.data.pagination.before_url(&page.uri) {
+                self.links.insert(
+                    before.to_lowercase(),
+                    listing_file(&page.subreddit, page.number - 1),
+                );
+            }
+        }
+    }
+
+    async fn save_thread(&mut self, data: CommentsQuery) -> anyhow::Result<()> {
+        let post = &data.post;
+        let (subreddit, id) = (post.subreddit.clone(), post.id.clone());
+
+        let saved = SavedThread {
+            subreddit: subreddit.clone(),
+            title: post.title.clone(),
+            num_comments: post.num_comments,
+            saved: chrono::Utc::now().timestamp(),
+        };
+
+        let uri = format!("/r/{}/comments/{}", subreddit, id).parse()?;
+        let template = CommentsTemplate::new(subreddit.clone(), data, uri, self.theme.clone());
+        self.save_page(&thread_file(&subreddit, &id), template)
+            .await?;
+
+        // written as we go, so an interrupted run resumes where it stopped
+        self.manifest.threads.insert(id, saved);
+        self.save_manifest()
+    }
+
+    async fn save_listing(&self, page: ListingPage) -> anyhow::Result<()> {
+        let file = listing_file(&page.subreddit, page.number);
+        let template =
+            SubredditTemplate::new(page.subreddit, page.data, page.uri, self.theme.clone());
+
+        self.save_page(&file, template).await
+    }
+
+    async fn save_index(&self) -> anyhow::Result<()> {
+        let mut threads: Vec<(&str, &SavedThread)> = self
+            .manifest
+            .threads
+            .iter()
+            .map(|(id, t)| (id.as_str(), t))
+            .collect();
+        threads.sort_by_key(|(_, t)| std::cmp::Reverse(t.saved));
+
+        let template = ArchiveIndexTemplate {
+            subreddits: self.manifest.subreddits.keys().cloned().collect(),
+            threads,
+            theme: self.theme.clone(),
+        };
+
+        self.save_page("index.html", template).await
+    }
+
+    /// Renders a page, points its links into the archive and downloads what it shows.
+    async fn save_page(&self, file: &str, template: impl Template) -> anyhow::Result<()> {
+        let html = template.render()?;
+
+        let mut media = BTreeSet::new();
+        let html = rewrite_links(&self.links, &html, file, &mut media);
+
+        for path in media {
+            self.save_media(&path).await?;
+        }
+
+        self.write(file, html.as_bytes())
+    }
+
+    /// Downloads an image or stylesheet, unless an earlier run did.
+    async fn save_media(&self, file: &str) -> anyhow::Result<()> {
+        // always written, as themes can change between runs
+        if let Some(theme) = file.strip_prefix("themes/") {
+            let name = theme.trim_end_matches(".css");
+            if let Some(css) = self.themes.css(name) {
+                self.write(file, css.as_bytes())?;
+            }
+            return Ok(());
+        }
+
+        if self.options.out.join(file).exists() {
+            return Ok(());
+        }
+
+        let url = match file.split_once('/') {
+            Some(("i", f)) => crate::image_proxy::reddit_image_url(f),
+            Some(("t", f)) => crate::image_proxy::reddit_thumbnail_url(f),
+            _ => return Ok(()),
+        };
+
+        // the client records it where the pages expect it
+        if let Err(e) = crate::image_proxy::fetch(&self.client, &url, USER_AGENT).await {
+            // the page is still worth having without it
+            tracing::warn!("Couldn't download {}: {}", url, e);
+        }
+        Ok(())
+    }
+
+    fn save_manifest(&self) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(&self.manifest)?;
+
+        // renamed into place, so a crash never leaves half a manifest
+        let tmp = format!("{}.tmp", MANIFEST_FILE);
+        self.write(&tmp, json.as_bytes())?;
+        std::fs::rename(
+            self.options.out.join(&tmp),
+            self.options.out.join(MANIFEST_FILE),
+        )?;
+
+        Ok(())
+    }
+
+    fn write(&self, file: &str, contents: &[u8]) -> anyhow::Result<()> {
+        let path = self.options.out.join(file);
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, contents)
+            .with_context(|| format!("Couldn't write {}", path.display()))
+    }
+}
+
+#[derive(Template)]
+#[template(path = "archive_index.html")]
+struct ArchiveIndexTemplate<'a> {
+    subreddits: Vec<String>,
+    /// (id, thread), most recently saved first
+    threads: Vec<(&'a str, &'a SavedThread)>,
+    theme: CurrentTheme,
+}
+
+/// Files are named after URLs, with `index.html` for browsers to open from `file://`.
+fn listing_file(subreddit: &str, page: usize) -> String {
+    let subreddit = subreddit.to_lowercase();
+
+    match page {
+        1 => format!("r/{}/index.html", subreddit),
+        n => format!("r/{}/page-{}.html", subreddit, n),
+    }
+}
+
+fn thread_file(subreddit: &str, id: &str) -> String {
+    format!("r/{}/comments/{}/index.html", subreddit.to_lowercase(), id)
+}
+
+/// Where images from the media proxy and theme stylesheets are saved.
+fn media_file(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+
+    let (kind, file) = path.trim_start_matches('/').split_once('/')?;
+    let safe = !file.is_empty()
+        && file
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+
+    match kind {
+        "i" | "t" | "themes" if safe => Some(format!("{}/{}", kind, file)),
+        _ => None,
+    }
+}
+
+/// Local links, relative to `from`, lead to archived pages in `links` or media,
+/// and to Reddit otherwise. Media to download is added to `media`.
+fn rewrite_links(
+    links: &HashMap<String, String>,
+    html: &str,
+    from: &str,
+    media: &mut BTreeSet<String>,
+) -> String {
+    let root = "../".repeat(from.matches('/').count());
+
+    let mut rewrite = |value: &str| {
+        let url = unescape(value);
+        if !url.starts_with('/') || url.starts_with("//") {
+            return None;
+        }
+
+        let target = if let Some(file) = links.get(&url.to_lowercase()) {
+            format!("{}{}", root, file)
+        } else if let Some(file) = media_file(&url) {
+            media.insert(file.clone());
+            format!("{}{}", root, file)
+        } else {
+            format!("{}{}", REDDIT, url)
+        };
+
+        Some(target.replace('&', "&amp;").replace('"', "&quot;"))
+    };
+
+    let mut html = html.to_string();
+    for attribute in ["href=\"", "src=\"", "action=\""] {
+        html = rewrite_attribute(&html, attribute, &mut rewrite);
+    }
+    html
+}
+
+/// Attribute values as askama escaped them.
+fn unescape(value: &str) -> String {
+    value
+        .replace("&#x2f;", "/")
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Replaces the values of `attribute` (like `href="`) for which `rewrite` returns something.
+fn rewrite_attribute(
+    html: &str,
+    attribute: &str,
+    rewrite: &mut impl FnMut(&str) -> Option<String>,
+) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(i) = rest.find(attribute) {
+        let (before, after) = rest.split_at(i + attribute.len());
+        out.push_str(before);
+
+        let end = after.find('"').unwrap_or(after.len());
+        let value = &after[..end];
+        out.push_str(&rewrite(value).unwrap_or_else(|| value.to_string()));
+
+        rest = &after[end..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets() {
+        assert_eq!(
+            Target::parse("r/rust"),
+            Some(Target::Subreddit("rust".to_string()))
+        );
+        assert_eq!(
+            Target::parse("/r/rust/comments/1abcde/some_title/"),
+            Some(Target::Thread {
+                subreddit: Some("rust".to_string()),
+                id: "1abcde".to_string(),
+            })
+        );
+        assert_eq!(
+            Target::parse("1abcde"),
+            Some(Target::Thread {
+                subreddit: None,
+                id: "1abcde".to_string(),
+            })
+        );
+        assert_eq!(Target::parse(""), None);
+        assert_eq!(Target::parse("u/spez"), None);
+        assert_eq!(Target::parse("r/.."), None);
+        assert_eq!(Target::parse("r/rust/comments/.."), None);
+        assert_eq!(Target::parse("r/../comments/1abcde"), None);
+        assert_eq!(Target::parse("a\\b"), None);
+    }
+
+    #[test]
+    fn media_files() {
+        assert_eq!(media_file("/i/abc.jpg?s=1"), Some("i/abc.jpg".to_string()));
+        assert_eq!(
+            media_file("/t/abc-def_1.png"),
+            Some("t/abc-def_1.png".to_string())
+        );
+        assert_eq!(
+            media_file("/themes/dark.css?v=1a2b"),
+            Some("themes/dark.css".to_string())
+        );
+        assert_eq!(media_file("/i/"), None);
+        assert_eq!(media_file("/i/../archive.json"), None);
+        assert_eq!(media_file("/i/a/b.jpg"), None);
+        assert_eq!(media_file("/r/rust"), None);
+        assert_eq!(media_file("/settings"), None);
+    }
+
+    #[test]
+    fn attributes_are_rewritten() {
+        // the last value isn't closed
+        let html = r#"<a href="/a">a</a> <img src="/b"> <a href="/b">b</a> <a href="/c"#;
+        let mut rewrite = |v: &str| (v != "/b").then(|| v.to_uppercase());
+
+        assert_eq!(
+            rewrite_attribute(html, "href=\"", &mut rewrite),
+            r#"<a href="/A">a</a> <img src="/b"> <a href="/b">b</a> <a href="/C"#
+        );
+    }
+
+    #[test]
+    fn links_lead_into_the_archive_or_to_reddit() {
+        let links = HashMap::from([(
+            "/r/rust/comments/1abcde".to_string(),
+            thread_file("rust", "1abcde"),
+        )]);
+        let html = concat!(
+            r#"<a href="/r/Rust/comments/1abcde">thread</a>"#,
+            r#"<img src="/i/abc.jpg">"#,
+            r#"<a href="/r/rust/top?t=all&amp;after=t3_x">top</a>"#,
+            r#"<a href="https://example.com">elsewhere</a>"#,
+            r#"<a href="//example.com">no scheme</a>"#,
+            r#"<img src="/i/..%2Farchive.json">"#,
+        );
+        let mut media = BTreeSet::new();
+
+        assert_eq!(
+            rewrite_links(&links, html, "r/rust/index.html", &mut media),
+            concat!(
+                r#"<a href="../../r/rust/comments/1abcde/index.html">thread</a>"#,
+                r#"<img src="../../i/abc.jpg">"#,
+                r#"<a href="https://www.reddit.com/r/rust/top?t=all&amp;after=t3_x">top</a>"#,
+                r#"<a href="https://example.com">elsewhere</a>"#,
+                r#"<a href="//example.com">no scheme</a>"#,
+                r#"<img src="https://www.reddit.com/i/..%2Farchive.json">"#,
+            )
+        );
+        assert_eq!(media, BTreeSet::from(["i/abc.jpg".to_string()]));
+    }
+}
@@ -0,0 +1,79 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{anyhow, bail};
+use older_reddit::archive::{Archiver, Options, Target};
+
+const USAGE: &str = "\
+usage: older-reddit-archive [--out DIR] [--depth PAGES] [--delay SECONDS] [--refresh] TARGET...
+
+Saves subreddits and threads as static HTML, browseable without a server.
+Running it again on the same directory resumes, and updates threads with new comments.
+What Reddit answered is kept too, so `older-reddit --offline DIR` can serve the archive.
+
+TARGET is r/<subreddit>, a thread id, or r/<subreddit>/comments/<id>.
+
+  --out DIR          where the archive goes, ./archive by default
+  --depth PAGES      listing pages saved per subreddit, 1 by default
+  --delay SECONDS    pause between requests to Reddit, 1 by default
+  --refresh          save every thread again, even unchanged ones";
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt::init();
+
+    if let Err(e) = run().await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
+    let (options, targets) = parse_args(std::env::args().skip(1))?;
+
+    let mut archiver = Archiver::new(options)?;
+    archiver.run(&targets).await
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> anyhow::Result<(Options, Vec<Target>)> {
+    let mut options = Options {
+        out: PathBuf::from("archive"),
+        depth: 1,
+        delay: Duration::from_secs(1),
+        refresh: false,
+    };
+    let mut targets = Vec::new();
+
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| anyhow!("{} needs a value\n\n{}", name, USAGE))
+        };
+
+        match arg.as_str() {
+            "--out" => options.out = PathBuf::from(value("--out")?),
+            "--depth" => options.depth = value("--depth")?.parse()?,
+            "--delay" => {
+                // negative, NaN and infinite pauses can't be waited for
+                options.delay = value("--delay")?
+                    .parse()
+                    .ok()
+                    .and_then(|s| Duration::try_from_secs_f64(s).ok())
+                    .ok_or_else(|| anyhow!("--delay needs a number of seconds\n\n{}", USAGE))?
+            }
+            "--refresh" => options.refresh = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            a if a.starts_with('-') => bail!("Unknown option {}\n\n{}", a, USAGE),
+            a => targets.push(Target::parse(a).ok_or_else(|| anyhow!("Not a target: {}", a))?),
+        }
+    }
+
+    if targets.is_empty() {
+        bail!("Nothing to archive\n\n{}", USAGE);
+    }
+
+    Ok((options, targets))
+}
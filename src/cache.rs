@@ -0,0 +1,48 @@
+use std::{borrow::Borrow, collections::HashMap, hash::Hash, sync::Arc};
+
+use tokio::sync::RwLock;
+
+/// A map shared between requests that is emptied when it fills up.
+/// Meant for answers from Reddit that are cheap to ask for again,
+/// where keeping the most used ones isn't worth the bookkeeping.
+#[derive(Clone)]
+pub struct BoundedCache<K, V> {
+    /// Label of its lookups in the metrics
+    name: &'static str,
+    capacity: usize,
+    entries: Arc<RwLock<HashMap<K, V>>>,
+}
+
+impl<K: Eq + Hash, V: Clone> BoundedCache<K, V> {
+    pub fn new(name: &'static str, capacity: usize) -> Self {
+        BoundedCache {
+            name,
+            capacity,
+            entries: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// Number of entries, out of the capacity.
+    pub async fn entries(&self) -> usize {
+        self.entries.read().await.len()
+    }
+
+    /// Counted as a hit or a miss in the metrics.
+    pub async fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        let value = self.entries.read().await.get(key).cloned();
+        crate::metrics::cache_lookup(self.name, value.is_some());
+        value
+    }
+
+    pub async fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.write().await;
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.clear();
+        }
+        entries.insert(key, value);
+    }
+}
@@ -3,15 +3,23 @@ use axum::{
     extract::{Path, Query, State},
     headers::UserAgent,
     http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
     TypedHeader,
 };
-use reqwest::Client;
 use serde::Deserialize;
 
 use crate::{
     api::CommentsQuery,
     api_result_types::{RedditData, ReplyList},
     api_types::CommentSortingMode,
+    export::ExportFormat,
+    feed::{BaseUrl, Feed, FeedFormat},
+    legacy::Layout,
+    nsfw::NsfwGate,
+    pagination::PageRequest,
+    preferences::Preferences,
+    themes::CurrentTheme,
+    upstream::Client,
 };
 
 #[derive(Template)]
@@ -20,24 +28,128 @@ pub struct CommentsTemplate {
     subreddit: String,
     data: CommentsQuery,
     gallery_index: usize,
-    uri: Uri
+    /// Spoiler posts are hidden until asked for
+    show_spoiler: bool,
+    prefs: Preferences,
+    uri: Uri,
+    theme: CurrentTheme,
 }
 
+impl CommentsTemplate {
+    /// A thread outside of any request, as the archiver saves them: spoilers shown, default preferences.
+    pub fn new(subreddit: String, data: CommentsQuery, uri: Uri, theme: CurrentTheme) -> Self {
+        CommentsTemplate {
+            subreddit,
+            data,
+            gallery_index: 0,
+            show_spoiler: true,
+            prefs: Preferences::default(),
+            uri,
+            theme,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "legacy/comments.html")]
+pub struct LegacyCommentsTemplate {
+    prefix: &'static str,
+    subreddit: String,
+    data: CommentsQuery,
+    show_spoiler: bool,
+    uri: Uri,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn comments(
     Path((subreddit, id)): Path<(String, String)>,
     Query(params): Query<CommentsParams>,
+    Query(page): Query<PageRequest>,
     TypedHeader(user_agent): TypedHeader<UserAgent>,
     State(client): State<Client>,
-    uri: Uri
-) -> Result<CommentsTemplate, StatusCode> {
+    prefs: Preferences,
+    gate: NsfwGate,
+    uri: Uri,
+    base: BaseUrl,
+    theme: CurrentTheme,
+    layout: Layout,
+) -> Result<Response, StatusCode> {
+    let (id, format) = FeedFormat::split(&id);
+
+    let data = crate::api::comments(&client, &subreddit, id, None, params.sorting, &page, &prefs, user_agent.as_str()).await?;
+
+    if let Some(format) = format {
+        if let Some(blocked) = gate.check_disabled(data.post.over_18) {
+            return Ok(blocked);
+        }
+
+        let feed = Feed::from_comments(format!("/r/{}/comments/{}", subreddit, id), &data);
+        return Ok(feed.into_response(format, base));
+    }
+
+    if let Some(format) = params.format {
+        if let Some(blocked) = gate.check_disabled(data.post.over_18) {
+            return Ok(blocked);
+        }
+
+        return crate::export::export(&client, &data, format, &base, user_agent.as_str()).await;
+    }
+
+    if let Some(warning) = gate.check(data.post.over_18, &uri) {
+        return Ok(warning);
+    }
+
+    if let Layout::Legacy { prefix } = layout {
+        return Ok(crate::legacy::render(LegacyCommentsTemplate { prefix, subreddit, data, show_spoiler: params.spoiler.unwrap_or_default(), uri }));
+    }
+
+    Ok(CommentsTemplate { subreddit, data, gallery_index: params.gallery_index.unwrap_or_default(), show_spoiler: params.spoiler.unwrap_or_default(), prefs, uri, theme }.into_response())
+}
+
+/// Reddit permalinks, `/r/:subreddit/comments/:id/:title/:comment_id`.
+/// Only the thread under the comment is shown, if there is one.
+#[allow(clippy::too_many_arguments)]
+pub async fn permalink(
+    Path((subreddit, id, rest)): Path<(String, String, String)>,
+    Query(params): Query<CommentsParams>,
+    Query(page): Query<PageRequest>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+    prefs: Preferences,
+    gate: NsfwGate,
+    uri: Uri,
+    base: BaseUrl,
+    theme: CurrentTheme,
+    layout: Layout,
+) -> Result<Response, StatusCode> {
+    let comment_id = rest.split('/').filter(|s| !s.is_empty()).nth(1);
+
+    let data = crate::api::comments(&client, &subreddit, &id, comment_id, params.sorting, &page, &prefs, user_agent.as_str()).await?;
+
+    if let Some(format) = params.format {
+        if let Some(blocked) = gate.check_disabled(data.post.over_18) {
+            return Ok(blocked);
+        }
+
+        return crate::export::export(&client, &data, format, &base, user_agent.as_str()).await;
+    }
+
+    if let Some(warning) = gate.check(data.post.over_18, &uri) {
+        return Ok(warning);
+    }
+
+    if let Layout::Legacy { prefix } = layout {
+        return Ok(crate::legacy::render(LegacyCommentsTemplate { prefix, subreddit, data, show_spoiler: params.spoiler.unwrap_or_default(), uri }));
+    }
 
-    let data = crate::api::comments(&client, &subreddit, &id, params.sorting, user_agent.as_str()).await?;
-    dbg!(data.get_post_type());
-    Ok(CommentsTemplate { subreddit, data, gallery_index: params.gallery_index.unwrap_or_default(), uri })
+    Ok(CommentsTemplate { subreddit, data, gallery_index: params.gallery_index.unwrap_or_default(), show_spoiler: params.spoiler.unwrap_or_default(), prefs, uri, theme }.into_response())
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct CommentsParams {
     gallery_index: Option<usize>,
-    sorting: Option<CommentSortingMode>
+    sorting: Option<CommentSortingMode>,
+    spoiler: Option<bool>,
+    /// Downloads the thread instead
+    format: Option<ExportFormat>,
 }
\ No newline at end of file
@@ -0,0 +1,64 @@
+use axum::http::{header, HeaderMap, HeaderValue};
+use cookie::{time::Duration, Cookie, CookieJar, Key, SameSite};
+
+/// Environment variable holding the key cookies are signed with.
+/// Must be at least 64 bytes long.
+const KEY_VAR: &str = "OLDER_REDDIT_COOKIE_KEY";
+
+/// Key cookies are signed with.
+/// Without a configured one, a random key is used and cookies don't survive restarts.
+pub fn key() -> anyhow::Result<Key> {
+    match std::env::var(KEY_VAR) {
+        Ok(k) => Ok(Key::try_from(k.as_bytes())?),
+        Err(_) => {
+            tracing::warn!(
+                "{} is not set, preferences and subscriptions will be lost when the server restarts",
+                KEY_VAR
+            );
+            Ok(Key::generate())
+        }
+    }
+}
+
+/// Value of a signed cookie from the request.
+/// Missing, unsigned or tampered cookies are ignored.
+pub fn get_signed(headers: &HeaderMap, key: &Key, name: &str) -> Option<String> {
+    let mut jar = CookieJar::new();
+
+    for value in headers.get_all(header::COOKIE) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+
+        for cookie in Cookie::split_parse_encoded(value.to_string()).flatten() {
+            jar.add_original(cookie.into_owned());
+        }
+    }
+
+    let cookie = jar.signed(key).get(name)?;
+    Some(cookie.value().to_string())
+}
+
+/// `Set-Cookie` header storing a signed cookie for a year.
+pub fn set_signed(key: &Key, name: &'static str, value: String) -> Option<HeaderValue> {
+    let cookie = Cookie::build(name, value)
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .max_age(Duration::days(365))
+        .finish();
+
+    let mut jar = CookieJar::new();
+    jar.signed_mut(key).add(cookie);
+
+    let cookie = jar.get(name)?;
+    HeaderValue::from_str(&cookie.encoded().to_string()).ok()
+}
+
+/// `Set-Cookie` header removing a cookie.
+pub fn removal(name: &'static str) -> HeaderValue {
+    let mut cookie = Cookie::build(name, "").path("/").finish();
+    cookie.make_removal();
+
+    HeaderValue::from_str(&cookie.to_string()).expect("valid cookie")
+}
@@ -0,0 +1,47 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, StatusCode, Uri},
+};
+
+/// For forms that replace a cookie, which must come from our own pages.
+/// The cookies are `SameSite=Lax`, so a form another site posts arrives without them,
+/// and its handler would send back a cookie made from the other site's form instead.
+///
+/// Browsers send `Origin` with forms, or at least `Referer`, which must be this host.
+/// Requests with neither, from very old browsers, are let through.
+pub struct SameOrigin;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for SameOrigin
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let headers = &parts.headers;
+        let Some(source) = headers
+            .get(header::ORIGIN)
+            .or_else(|| headers.get(header::REFERER))
+        else {
+            return Ok(SameOrigin);
+        };
+
+        // `Origin: null` is sent from sandboxed and local pages, which aren't ours either
+        let source_host = source
+            .to_str()
+            .ok()
+            .and_then(|s| s.parse::<Uri>().ok())
+            .and_then(|u| u.authority().map(|a| a.as_str().to_string()));
+        let host = headers.get(header::HOST).and_then(|h| h.to_str().ok());
+
+        match (source_host, host) {
+            (Some(s), Some(h)) if s.eq_ignore_ascii_case(h) => Ok(SameOrigin),
+            _ => {
+                tracing::warn!("Refused a form posted from {:?}", source);
+                Err(StatusCode::FORBIDDEN)
+            }
+        }
+    }
+}
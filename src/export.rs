@@ -0,0 +1,399 @@
+use std::io::{Cursor, Write};
+
+use askama::Template;
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    api::CommentsQuery,
+    api_result_types::{T1Data, T3Data},
+    feed::BaseUrl,
+    upstream::Client,
+};
+
+/// Columns plain text exports are wrapped at.
+const TEXT_WIDTH: usize = 72;
+
+/// What a thread can be downloaded as, with `?format=` on the comments route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ExportFormat {
+    #[serde(rename = "md")]
+    Markdown,
+    #[serde(rename = "txt")]
+    Text,
+    #[serde(rename = "epub")]
+    Epub,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Text => "txt",
+            ExportFormat::Epub => "epub",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Text => "text/plain; charset=utf-8",
+            ExportFormat::Epub => "application/epub+zip",
+        }
+    }
+}
+
+/// The whole thread as a download.
+/// Images point at our media proxy, or are embedded in EPUBs.
+pub async fn export(
+    client: &Client,
+    data: &CommentsQuery,
+    format: ExportFormat,
+    base: &BaseUrl,
+    user_agent: &str,
+) -> Result<Response, StatusCode> {
+    let images = images(&data.post);
+
+    let body = match format {
+        ExportFormat::Markdown => render(MarkdownExport {
+            data,
+            header: Header::new(&data.post),
+            base: &base.0,
+            images: &images,
+        })?
+        .into_bytes(),
+        ExportFormat::Text => render(TextExport {
+            data,
+            header: Header::new(&data.post),
+            base: &base.0,
+            images: &images,
+        })?
+        .into_bytes(),
+        ExportFormat::Epub => epub(client, data, &images, user_agent).await?,
+    };
+
+    let filename = format!(
+        "attachment; filename=\"{}-{}.{}\"",
+        data.post.subreddit,
+        data.post.id,
+        format.extension()
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, filename),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Files of the post's images on `i.redd.it`, as served under `/i/`.
+/// The names come from Reddit and end up in URLs and EPUB entries,
+/// those that aren't plain file names are left out.
+fn images(post: &T3Data) -> Vec<String> {
+    let files = match &post.gallery_data {
+        Some(gallery) => gallery.items.iter().map(|i| post.gallery_file(i)).collect(),
+        None => post
+            .url
+            .as_deref()
+            .and_then(|u| u.strip_prefix("https://i.redd.it/"))
+            .map(|f| vec![f.to_string()])
+            .unwrap_or_default(),
+    };
+
+    files
+        .into_iter()
+        .filter(|f| crate::image_proxy::is_safe_file(f))
+        .collect()
+}
+
+fn render(template: impl Template) -> Result<String, StatusCode> {
+    template.render().map_err(|e| {
+        tracing::error!("Failed to render export: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+fn date(created_utc: f64) -> String {
+    crate::utils::format_timestamp(&(created_utc as u64))
+}
+
+/// What the post links to outside of Reddit; its images are listed on their own.
+fn link(post: &T3Data) -> Option<&str> {
+    if post.is_reddit_media_domain || post.domain.starts_with("self.") {
+        return None;
+    }
+    post.url.as_deref()
+}
+
+/// Post metadata every format shows.
+struct Header<'a> {
+    posted: String,
+    link: Option<&'a str>,
+}
+
+impl Header<'_> {
+    fn new(post: &T3Data) -> Header<'_> {
+        Header {
+            posted: date(post.created_utc),
+            link: link(post),
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "export/thread.md", escape = "none")]
+struct MarkdownExport<'a> {
+    data: &'a CommentsQuery,
+    header: Header<'a>,
+    base: &'a str,
+    images: &'a [String],
+}
+
+impl MarkdownExport<'_> {
+    /// Replies are quoted once per level, so the nesting survives.
+    fn comment(&self, depth: &usize, comment: &T1Data) -> String {
+        let text = format!(
+            "**u/{}** - {} points - {}\n\n{}",
+            comment.author,
+            comment.score,
+            date(comment.created_utc),
+            comment.body.trim()
+        );
+
+        let quote = "> ".repeat(*depth);
+        text.lines()
+            .map(|l| format!("{}{}", quote, l).trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[derive(Template)]
+#[template(path = "export/thread.txt", escape = "none")]
+struct TextExport<'a> {
+    data: &'a CommentsQuery,
+    header: Header<'a>,
+    base: &'a str,
+    images: &'a [String],
+}
+
+impl TextExport<'_> {
+    fn selftext(&self) -> String {
+        self.text(&0, &self.data.post.selftext)
+    }
+
+    /// Markdown as wrapped plain text, indented `depth` levels.
+    fn text(&self, depth: &usize, markdown: &str) -> String {
+        let indent = "    ".repeat(*depth);
+        crate::utils::wrap_text(&crate::gemtext::plain_text(markdown), &indent, TEXT_WIDTH)
+            .iter()
+            .map(|l| l.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn byline(&self, depth: &usize, comment: &T1Data) -> String {
+        format!(
+            "{}u/{} - {} points - {}",
+            "    ".repeat(*depth),
+            comment.author,
+            comment.score,
+            date(comment.created_utc)
+        )
+    }
+}
+
+/// An image embedded in an EPUB.
+struct EpubImage {
+    file: String,
+    media_type: String,
+}
+
+#[derive(Template)]
+#[template(path = "export/epub/content.opf", escape = "html")]
+struct EpubPackage<'a> {
+    post: &'a T3Data,
+    images: &'a [EpubImage],
+    modified: String,
+}
+
+#[derive(Template)]
+#[template(path = "export/epub/nav.xhtml", escape = "html")]
+struct EpubNav<'a> {
+    post: &'a T3Data,
+}
+
+#[derive(Template)]
+#[template(path = "export/epub/thread.xhtml", escape = "html")]
+struct EpubThread<'a> {
+    data: &'a CommentsQuery,
+    header: Header<'a>,
+    images: &'a [EpubImage],
+}
+
+impl EpubThread<'_> {
+    fn date(&self, comment: &T1Data) -> String {
+        date(comment.created_utc)
+    }
+}
+
+/// EPUB 3: a zip whose `mimetype` comes first, uncompressed.
+async fn epub(
+    client: &Client,
+    data: &CommentsQuery,
+    images: &[String],
+    user_agent: &str,
+) -> Result<Vec<u8>, StatusCode> {
+    let mut embedded = Vec::new();
+    let mut image_files = Vec::new();
+
+    for file in images {
+        let url = crate::image_proxy::reddit_image_url(file);
+
+        // the thread is still worth having without them
+        match crate::image_proxy::fetch(client, &url, user_agent).await {
+            Ok((media_type, bytes)) => {
+                embedded.push(EpubImage {
+                    file: file.clone(),
+                    media_type: media_type.unwrap_or_else(|| "image/jpeg".to_string()),
+                });
+                image_files.push(bytes);
+            }
+            Err(e) => tracing::warn!("Couldn't embed {} in an EPUB: {}", url, e),
+        }
+    }
+
+    let package = render(EpubPackage {
+        post: &data.post,
+        images: &embedded,
+        modified: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+    })?;
+    let nav = render(EpubNav { post: &data.post })?;
+    let thread = render(EpubThread {
+        data,
+        header: Header::new(&data.post),
+        images: &embedded,
+    })?;
+
+    let mut files: Vec<(String, &[u8])> = vec![
+        (
+            "META-INF/container.xml".to_string(),
+            CONTAINER_XML.as_bytes(),
+        ),
+        ("OEBPS/content.opf".to_string(), package.as_bytes()),
+        ("OEBPS/nav.xhtml".to_string(), nav.as_bytes()),
+        ("OEBPS/thread.xhtml".to_string(), thread.as_bytes()),
+        ("OEBPS/style.css".to_string(), EPUB_CSS.as_bytes()),
+    ];
+
+    for (image, bytes) in embedded.iter().zip(&image_files) {
+        files.push((format!("OEBPS/images/{}", image.file), bytes));
+    }
+
+    zip(&files).map_err(|e| {
+        tracing::error!("Failed to write EPUB: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Tells readers where the package document is.
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+const EPUB_CSS: &str = "\
+.meta { color: #666; font-size: 0.85em; }
+.comment { border-left: 2px solid #ccc; padding-left: 0.6em; margin: 0.8em 0; }
+img { max-width: 100%; }
+";
+
+fn zip(files: &[(String, &[u8])]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+
+    zip.start_file(
+        "mimetype",
+        FileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(b"application/epub+zip")?;
+
+    for (name, contents) in files {
+        zip.start_file(name.as_str(), FileOptions::default())?;
+        zip.write_all(contents)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+mod filters {
+    use pulldown_cmark::{Event, Options, Parser};
+
+    /// Reddit markdown to XHTML, which unlike HTML closes every tag.
+    /// Raw HTML is escaped, like the `markdown` filter does.
+    pub fn xhtml<T: std::fmt::Display>(markdown: T) -> askama::Result<String> {
+        let markdown = markdown.to_string();
+        let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+
+        let events = Parser::new_ext(&markdown, options).map(|e| match e {
+            Event::Html(html) => Event::Text(html),
+            e => e,
+        });
+
+        let mut out = String::new();
+        pulldown_cmark::html::push_html(&mut out, events);
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(fields: serde_json::Value) -> T3Data {
+        serde_json::from_value(fields).unwrap()
+    }
+
+    #[test]
+    fn gallery_images_keep_their_type() {
+        let post = post(serde_json::json!({
+            "gallery_data": {"items": [{"media_id": "k3h8zq"}, {"media_id": "p0w2xq"}, {"media_id": "a9c4xq"}]},
+            "media_metadata": {
+                "k3h8zq": {"status": "valid", "m": "image/png"},
+                "p0w2xq": {"status": "valid", "m": "image/jpeg"},
+                "a9c4xq": {"status": "failed"}
+            }
+        }));
+
+        assert_eq!(images(&post), ["k3h8zq.png", "p0w2xq.jpg", "a9c4xq.jpg"]);
+    }
+
+    #[test]
+    fn image_names_are_plain_files() {
+        let gallery = post(serde_json::json!({
+            "gallery_data": {"items": [{"media_id": "../../META-INF/x"}, {"media_id": "ok"}]},
+        }));
+        assert_eq!(images(&gallery), ["ok.jpg"]);
+
+        for url in [
+            "https://i.redd.it/../secret.jpg",
+            "https://i.redd.it/a/b.jpg",
+            "https://i.redd.it/f3rr1s.jpg?width=640",
+        ] {
+            let image = post(serde_json::json!({ "url": url }));
+            assert!(images(&image).is_empty(), "{}", url);
+        }
+
+        let image = post(serde_json::json!({ "url": "https://i.redd.it/f3rr1s.jpg" }));
+        assert_eq!(images(&image), ["f3rr1s.jpg"]);
+    }
+}
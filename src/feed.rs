@@ -0,0 +1,234 @@
+use askama::Template;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+
+use crate::{
+    api::{CommentsQuery, SubredditQuery, UserQuery},
+    api_result_types::{RedditData, T1Data, T3Data},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+}
+
+impl FeedFormat {
+    /// Splits a path segment like `all.rss` into `all` and its feed format, if any.
+    pub fn split(segment: &str) -> (&str, Option<FeedFormat>) {
+        if let Some(s) = segment.strip_suffix(".rss") {
+            (s, Some(FeedFormat::Rss))
+        } else if let Some(s) = segment.strip_suffix(".atom") {
+            (s, Some(FeedFormat::Atom))
+        } else {
+            (segment, None)
+        }
+    }
+}
+
+/// Scheme and host this server is reached at, as feeds need absolute links.
+pub struct BaseUrl(pub String);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for BaseUrl
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let host = parts
+            .headers
+            .get(header::HOST)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("localhost:3000");
+
+        let scheme = parts
+            .headers
+            .get("x-forwarded-proto")
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("http");
+
+        Ok(BaseUrl(format!("{}://{}", scheme, host)))
+    }
+}
+
+pub struct Feed {
+    pub title: String,
+    /// Local path of the page the feed is for
+    pub link: String,
+    pub items: Vec<FeedItem>,
+}
+
+pub struct FeedItem {
+    /// Fullname, stable across edits
+    pub id: String,
+    pub title: String,
+    /// Local path
+    pub link: String,
+    pub author: String,
+    pub published: DateTime<Utc>,
+    /// Sanitized HTML
+    pub content: String,
+}
+
+impl FeedItem {
+    fn from_post(post: &T3Data) -> FeedItem {
+        let mut content = render_markdown(&post.selftext);
+
+        if let Some(url) = &post.url {
+            let url = url.replace("https://i.redd.it", "/i");
+            content.push_str(&format!("<p><a href=\"{0}\">{0}</a></p>", escape(&url)));
+        }
+
+        FeedItem {
+            id: format!("t3_{}", post.id),
+            title: post.title.clone(),
+            link: format!("/r/{}/comments/{}", post.subreddit, post.id),
+            author: post.author.clone(),
+            published: timestamp(post.created_utc),
+            content,
+        }
+    }
+
+    fn from_comment(comment: &T1Data) -> FeedItem {
+        let title = match &comment.link_title {
+            Some(t) => format!("{} on {}", comment.author, t),
+            None => format!("Comment by {}", comment.author),
+        };
+
+        FeedItem {
+            id: format!("t1_{}", comment.id),
+            title,
+            link: comment.permalink.clone(),
+            author: comment.author.clone(),
+            published: timestamp(comment.created_utc),
+            content: render_markdown(&comment.body),
+        }
+    }
+}
+
+impl Feed {
+    pub fn from_posts(title: String, link: String, data: &SubredditQuery) -> Feed {
+        Feed {
+            title,
+            link,
+            items: data.posts.iter().map(FeedItem::from_post).collect(),
+        }
+    }
+
+    /// Top level comments of a thread
+    pub fn from_comments(link: String, data: &CommentsQuery) -> Feed {
+        Feed {
+            title: data.post.title.clone(),
+            link,
+            items: data.comments.iter().map(FeedItem::from_comment).collect(),
+        }
+    }
+
+    pub fn from_user(title: String, link: String, data: &UserQuery) -> Feed {
+        let items = data
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                RedditData::T3(post) => Some(FeedItem::from_post(post)),
+                RedditData::T1(comment) => Some(FeedItem::from_comment(comment)),
+                _ => None,
+            })
+            .collect();
+
+        Feed { title, link, items }
+    }
+
+    fn updated(&self) -> DateTime<Utc> {
+        self.items
+            .iter()
+            .map(|i| i.published)
+            .max()
+            .unwrap_or_else(Utc::now)
+    }
+
+    pub fn into_response(mut self, format: FeedFormat, base: BaseUrl) -> Response {
+        let updated = self.updated();
+
+        // feed readers don't know where relative links point to
+        for item in self.items.iter_mut() {
+            item.content = item
+                .content
+                .replace("href=\"/", &format!("href=\"{}/", base.0))
+                .replace("src=\"/", &format!("src=\"{}/", base.0));
+        }
+
+        let (rendered, content_type) = match format {
+            FeedFormat::Rss => (
+                RssTemplate {
+                    feed: self,
+                    base: base.0,
+                    updated,
+                }
+                .render(),
+                "application/rss+xml",
+            ),
+            FeedFormat::Atom => (
+                AtomTemplate {
+                    feed: self,
+                    base: base.0,
+                    updated,
+                }
+                .render(),
+                "application/atom+xml",
+            ),
+        };
+
+        match rendered {
+            Ok(body) => ([(header::CONTENT_TYPE, content_type)], body).into_response(),
+            Err(e) => {
+                tracing::error!("{}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "feed.rss.xml")]
+struct RssTemplate {
+    feed: Feed,
+    base: String,
+    updated: DateTime<Utc>,
+}
+
+/// Atom ids are tag URIs, followed by the fullname of entries and the local path of feeds,
+/// so they stay the same whichever host and scheme the feed is fetched with.
+const ATOM_ID: &str = "tag:reddit.com,2005:";
+
+#[derive(Template)]
+#[template(path = "feed.atom.xml")]
+struct AtomTemplate {
+    feed: Feed,
+    base: String,
+    updated: DateTime<Utc>,
+}
+
+fn timestamp(t: f64) -> DateTime<Utc> {
+    DateTime::from_timestamp(t as i64, 0).unwrap_or_default()
+}
+
+/// Same as the `markdown` filter, so raw HTML in posts is escaped.
+fn render_markdown(text: &str) -> String {
+    let text = crate::utils::rewrite_reddit_links(text);
+
+    match askama::filters::markdown(askama::Html, text, None) {
+        Ok(m) => m.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    askama::MarkupDisplay::new_unsafe(text, askama::Html).to_string()
+}
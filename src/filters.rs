@@ -0,0 +1,272 @@
+use std::{convert::Infallible, future::Future, sync::Arc};
+
+use anyhow::bail;
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+use cookie::Key;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    api::{SubredditQuery, UserQuery},
+    api_result_types::{RedditData, T1Data, T3Data},
+    nsfw::NsfwPolicy,
+    pagination::{PageRequest, Pagination},
+    preferences::Preferences,
+};
+
+/// Environment variable pointing at the server-wide blocklist.
+const BLOCKLIST_VAR: &str = "OLDER_REDDIT_BLOCKLIST";
+
+/// Most pages fetched to fill a page back up, so a filter matching everything stays cheap.
+const MAX_TOP_UP_FETCHES: usize = 3;
+
+/// What to hide. Everything is matched case-insensitively.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FilterRules {
+    pub subreddits: Vec<String>,
+    pub users: Vec<String>,
+    /// Subdomains are hidden too
+    pub domains: Vec<String>,
+    /// Matched anywhere in titles
+    pub keywords: Vec<String>,
+    pub flairs: Vec<String>,
+}
+
+impl FilterRules {
+    /// Reads a blocklist file, one `kind: value` rule per line, `#` starting comments:
+    ///
+    /// ```text
+    /// subreddit: example
+    /// user: spammer
+    /// domain: example.com
+    /// keyword: giveaway
+    /// flair: meme
+    /// ```
+    pub fn parse(text: &str) -> anyhow::Result<FilterRules> {
+        let mut rules = FilterRules::default();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let Some((kind, value)) = line.split_once(':') else {
+                bail!("line {}: expected `kind: value`", i + 1);
+            };
+
+            let list = match kind.trim() {
+                "subreddit" => &mut rules.subreddits,
+                "user" => &mut rules.users,
+                "domain" => &mut rules.domains,
+                "keyword" => &mut rules.keywords,
+                "flair" => &mut rules.flairs,
+                k => bail!("line {}: unknown rule `{}`", i + 1, k),
+            };
+
+            list.push(value.trim().to_lowercase());
+        }
+
+        Ok(rules)
+    }
+
+    /// The blocklist configured through the environment, if any.
+    pub fn blocklist() -> anyhow::Result<FilterRules> {
+        let Ok(path) = std::env::var(BLOCKLIST_VAR) else {
+            return Ok(FilterRules::default());
+        };
+
+        let rules = FilterRules::parse(&std::fs::read_to_string(&path)?)?;
+        tracing::info!("Loaded blocklist from {}", path);
+
+        Ok(rules)
+    }
+
+    fn hides_post(&self, post: &T3Data) -> bool {
+        let domain = post.domain.to_lowercase();
+        let title = post.title.to_lowercase();
+
+        contains(&self.subreddits, &post.subreddit)
+            || contains(&self.users, &post.author)
+            || self
+                .domains
+                .iter()
+                .any(|d| domain == *d || domain.ends_with(&format!(".{}", d)))
+            || self.keywords.iter().any(|k| title.contains(k.as_str()))
+            || post
+                .link_flair_text
+                .as_ref()
+                .is_some_and(|f| contains(&self.flairs, f))
+    }
+
+    fn hides_comment(&self, comment: &T1Data) -> bool {
+        let title = comment
+            .link_title
+            .as_deref()
+            .unwrap_or_default()
+            .to_lowercase();
+
+        contains(&self.subreddits, &comment.subreddit)
+            || contains(&self.users, &comment.author)
+            || self.keywords.iter().any(|k| title.contains(k.as_str()))
+    }
+}
+
+fn contains(rules: &[String], value: &str) -> bool {
+    rules.iter().any(|r| r.eq_ignore_ascii_case(value))
+}
+
+/// Server-wide rules, applied on top of everyone's own.
+#[derive(Debug, Clone, Default)]
+pub struct Blocklist(pub Arc<FilterRules>);
+
+/// Everything hidden from a request: the user's rules, the blocklist and NSFW posts if asked.
+pub struct ContentFilter {
+    user: FilterRules,
+    server: Blocklist,
+    hide_nsfw: bool,
+    /// Whether to fetch more pages to make up for hidden posts
+    top_up: bool,
+}
+
+impl ContentFilter {
+    /// Only what the server hides, for frontends without preferences.
+    pub fn server(blocklist: Blocklist, policy: NsfwPolicy) -> ContentFilter {
+        ContentFilter {
+            user: FilterRules::default(),
+            server: blocklist,
+            hide_nsfw: policy.disabled,
+            top_up: false,
+        }
+    }
+
+    fn hides_post(&self, post: &T3Data) -> bool {
+        (self.hide_nsfw && post.over_18)
+            || self.user.hides_post(post)
+            || self.server.0.hides_post(post)
+    }
+
+    fn hides_comment(&self, comment: &T1Data) -> bool {
+        self.user.hides_comment(comment) || self.server.0.hides_comment(comment)
+    }
+
+    /// Hides what the rules match from `data`.
+    /// With top-up on, the following pages are fetched with `fetch` until it's full again.
+    pub async fn apply<L, F, Fut>(&self, data: &mut L, fetch: F)
+    where
+        L: Listing,
+        F: Fn(PageRequest) -> Fut,
+        Fut: Future<Output = Result<L, axum::http::StatusCode>>,
+    {
+        data.hide(self);
+
+        if !self.top_up {
+            return;
+        }
+
+        let wanted = data.pagination().fetched();
+
+        for _ in 0..MAX_TOP_UP_FETCHES {
+            if data.shown() >= wanted {
+                break;
+            }
+
+            let Some(next) = data.pagination().next_page() else {
+                break;
+            };
+
+            // what we have is still worth showing
+            let Ok(mut more) = fetch(next).await else {
+                break;
+            };
+
+            more.hide(self);
+            data.append(more);
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ContentFilter
+where
+    S: Send + Sync,
+    Key: FromRef<S>,
+    Blocklist: FromRef<S>,
+    NsfwPolicy: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let prefs = Preferences::from_request_parts(parts, state).await?;
+
+        Ok(ContentFilter {
+            user: prefs.filters,
+            server: Blocklist::from_ref(state),
+            hide_nsfw: prefs.hide_nsfw || NsfwPolicy::from_ref(state).disabled,
+            top_up: prefs.top_up,
+        })
+    }
+}
+
+/// Listings filters apply to.
+pub trait Listing: Sized {
+    /// Removes what `filter` hides, counting it.
+    fn hide(&mut self, filter: &ContentFilter);
+    /// Number of items left to show
+    fn shown(&self) -> usize;
+    fn pagination(&self) -> &Pagination;
+    /// Adds the page after this one.
+    fn append(&mut self, next: Self);
+}
+
+impl Listing for SubredditQuery {
+    fn hide(&mut self, filter: &ContentFilter) {
+        let before = self.posts.len();
+        self.posts.retain(|p| !filter.hides_post(p));
+        self.hidden += before - self.posts.len();
+    }
+
+    fn shown(&self) -> usize {
+        self.posts.len()
+    }
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn append(&mut self, next: Self) {
+        self.posts.extend(next.posts);
+        self.hidden += next.hidden;
+        self.pagination.extend(&next.pagination);
+    }
+}
+
+impl Listing for UserQuery {
+    fn hide(&mut self, filter: &ContentFilter) {
+        let before = self.children.len();
+        self.children.retain(|c| match c {
+            RedditData::T3(post) => !filter.hides_post(post),
+            RedditData::T1(comment) => !filter.hides_comment(comment),
+            _ => true,
+        });
+        self.hidden += before - self.children.len();
+    }
+
+    fn shown(&self) -> usize {
+        self.children.len()
+    }
+
+    fn pagination(&self) -> &Pagination {
+        &self.pagination
+    }
+
+    fn append(&mut self, next: Self) {
+        self.children.extend(next.children);
+        self.hidden += next.hidden;
+        self.pagination.extend(&next.pagination);
+    }
+}
@@ -0,0 +1,481 @@
+use std::{fs::File, io::BufReader, path::Path, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, bail};
+use askama::Template;
+use axum::http::{StatusCode, Uri};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::{rustls, TlsAcceptor};
+
+use crate::{
+    api::{CommentsQuery, SubredditQuery, UserQuery},
+    api_result_types::{RedditData, WikiPageData},
+    filters::ContentFilter,
+    pagination::PageRequest,
+    preferences::Preferences,
+    state::AppState,
+    subscriptions::Subscriptions,
+};
+
+/// Environment variable with the address to serve Gemini on, e.g. `0.0.0.0:1965`.
+/// Gemini is off without it.
+const ADDR_VAR: &str = "OLDER_REDDIT_GEMINI";
+
+/// Environment variable with the hostname the certificate is made for, `localhost` by default.
+const HOST_VAR: &str = "OLDER_REDDIT_GEMINI_HOST";
+
+/// Environment variable with the directory the certificate is kept in, the working directory by default.
+const CERT_DIR_VAR: &str = "OLDER_REDDIT_GEMINI_CERTS";
+
+const CERT_FILE: &str = "gemini-cert.pem";
+const KEY_FILE: &str = "gemini-key.pem";
+
+/// Gemini clients don't send one, so Reddit gets ours.
+const USER_AGENT: &str = concat!("older-reddit/", env!("CARGO_PKG_VERSION"), " (gemini)");
+
+/// Longest request the spec allows, without the CRLF.
+const MAX_REQUEST_LEN: usize = 1024;
+
+/// Time clients get to finish the handshake and send their request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serves Gemini if it's configured, returns right away otherwise.
+pub async fn serve(state: AppState) -> anyhow::Result<()> {
+    let Ok(addr) = std::env::var(ADDR_VAR) else {
+        return Ok(());
+    };
+
+    let host = std::env::var(HOST_VAR).unwrap_or_else(|_| "localhost".to_string());
+    let dir = std::env::var(CERT_DIR_VAR).unwrap_or_else(|_| ".".to_string());
+    let acceptor = TlsAcceptor::from(Arc::new(tls_config(&host, Path::new(&dir))?));
+
+    let listener = TcpListener::bind(&addr).await?;
+    tracing::info!("Serving Gemini on {}...", listener.local_addr()?);
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::error!("Failed to accept a Gemini connection: {}", e);
+                continue;
+            }
+        };
+
+        let (acceptor, state) = (acceptor.clone(), state.clone());
+
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, acceptor, &state).await {
+                tracing::debug!("Gemini connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+/// The certificate in `dir`, made and saved there the first time.
+/// Gemini clients remember the first certificate they see for a host, so it has to last.
+fn tls_config(host: &str, dir: &Path) -> anyhow::Result<rustls::ServerConfig> {
+    let cert_path = dir.join(CERT_FILE);
+    let key_path = dir.join(KEY_FILE);
+
+    if !cert_path.exists() || !key_path.exists() {
+        let cert = rcgen::generate_simple_self_signed(vec![host.to_string()])?;
+
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(&cert_path, cert.serialize_pem()?)?;
+        write_private(&key_path, &cert.serialize_private_key_pem())?;
+
+        tracing::info!(
+            "Generated a self-signed Gemini certificate for {} in {}",
+            host,
+            dir.display()
+        );
+    }
+
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(&cert_path)?))?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+
+    let key = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(File::open(&key_path)?))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("{} has no PKCS#8 private key", key_path.display()))?;
+
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, rustls::PrivateKey(key))?;
+
+    Ok(config)
+}
+
+/// Writes a file only its owner can read.
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    std::io::Write::write_all(&mut options.open(path)?, contents.as_bytes())
+}
+
+async fn handle(stream: TcpStream, acceptor: TlsAcceptor, state: &AppState) -> anyhow::Result<()> {
+    let accepted = tokio::time::timeout(REQUEST_TIMEOUT, async {
+        let mut stream = acceptor.accept(stream).await?;
+        let request = read_request(&mut stream).await;
+        anyhow::Ok((stream, request))
+    })
+    .await;
+
+    let (mut stream, request) = accepted.map_err(|_| anyhow!("timed out"))??;
+
+    let response = match request {
+        Ok(request) => respond(&request, state).await,
+        Err(e) => Response::error(59, &e.to_string()),
+    };
+
+    stream.write_all(&response.into_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+/// Reads the request line, the URL being asked for.
+async fn read_request(stream: &mut (impl AsyncRead + Unpin)) -> anyhow::Result<String> {
+    let mut request = Vec::new();
+    let mut buf = [0; 256];
+
+    loop {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            bail!("Connection closed before the end of the request");
+        }
+
+        request.extend_from_slice(&buf[..n]);
+
+        if let Some(end) = request.windows(2).position(|w| w == b"\r\n") {
+            request.truncate(end);
+            return Ok(String::from_utf8(request)?);
+        }
+
+        if request.len() > MAX_REQUEST_LEN + 2 {
+            bail!("Request too long");
+        }
+    }
+}
+
+/// Status line and body sent back.
+struct Response {
+    status: u8,
+    meta: String,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn page(gemtext: String) -> Response {
+        Response {
+            status: 20,
+            meta: "text/gemini; charset=utf-8".to_string(),
+            body: gemtext.into_bytes(),
+        }
+    }
+
+    /// Asks the user to type something, sent back as the query.
+    fn input(prompt: &str) -> Response {
+        Response::error(10, prompt)
+    }
+
+    fn redirect(url: String) -> Response {
+        Response {
+            status: 30,
+            meta: url,
+            body: Vec::new(),
+        }
+    }
+
+    fn error(status: u8, meta: &str) -> Response {
+        Response {
+            status,
+            meta: meta.to_string(),
+            body: Vec::new(),
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut bytes = format!("{} {}\r\n", self.status, self.meta).into_bytes();
+        bytes.extend(self.body);
+        bytes
+    }
+}
+
+impl From<StatusCode> for Response {
+    fn from(status: StatusCode) -> Self {
+        match status {
+            StatusCode::NOT_FOUND => Response::error(51, "Not found"),
+            StatusCode::FORBIDDEN => Response::error(51, "Not available"),
+            StatusCode::INTERNAL_SERVER_ERROR => Response::error(40, "Something went wrong"),
+            _ => Response::error(43, "Reddit couldn't be reached"),
+        }
+    }
+}
+
+/// Routes a request, mirroring the HTTP routes.
+async fn respond(request: &str, state: &AppState) -> Response {
+    let Ok(url) = reqwest::Url::parse(request) else {
+        return Response::error(59, "Not a URL");
+    };
+
+    if url.scheme() != "gemini" {
+        return Response::error(53, "Only Gemini is served here");
+    }
+
+    let segments: Vec<&str> = url
+        .path_segments()
+        .map(|s| s.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+
+    let uri = match url.query() {
+        Some(q) => format!("{}?{}", url.path(), q),
+        None => url.path().to_string(),
+    };
+    let Ok(uri) = uri.parse::<Uri>() else {
+        return Response::error(59, "Bad path");
+    };
+
+    let page = match segments.as_slice() {
+        [] => HomePage.render_page(),
+        ["go"] => return go(&url),
+        ["r", subreddit] => subreddit_page(state, subreddit, &uri).await,
+        ["r", subreddit, "comments", id, ..] => comments_page(state, subreddit, id, &uri).await,
+        ["r", subreddit, "wiki"] => wiki_page(state, subreddit, "index").await,
+        ["r", subreddit, "wiki", "pages"] => wiki_pages_page(state, subreddit).await,
+        ["r", subreddit, "wiki", page @ ..] => wiki_page(state, subreddit, &page.join("/")).await,
+        ["u", username] | ["user", username] => user_page(state, username, &uri).await,
+        ["i", file] => return image(state, file).await,
+        _ => Err(StatusCode::NOT_FOUND),
+    };
+
+    match page {
+        Ok(gemtext) => Response::page(gemtext),
+        Err(e) => e.into(),
+    }
+}
+
+/// Asks for a subreddit and goes there.
+fn go(url: &reqwest::Url) -> Response {
+    // the query is the raw input, which form decoding reads as a lone key
+    match url.query_pairs().next() {
+        Some((name, _)) if !name.trim().is_empty() => {
+            let name = name.trim().trim_start_matches("r/");
+            if !crate::utils::is_subreddit_name(name) {
+                return Response::error(59, "Not a subreddit name");
+            }
+            Response::redirect(format!("/r/{}", name))
+        }
+        _ => Response::input("Subreddit"),
+    }
+}
+
+trait RenderPage {
+    /// Renders the page, or an error response.
+    fn render_page(&self) -> Result<String, StatusCode>;
+}
+
+impl<T: Template> RenderPage for T {
+    fn render_page(&self) -> Result<String, StatusCode> {
+        self.render().map_err(|e| {
+            tracing::error!("Failed to render Gemini page: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+    }
+}
+
+#[derive(Template)]
+#[template(path = "gemini/home.gmi", escape = "none")]
+struct HomePage;
+
+#[derive(Template)]
+#[template(path = "gemini/subreddit.gmi", escape = "none")]
+struct SubredditPage {
+    subreddit: String,
+    data: SubredditQuery,
+    uri: Uri,
+}
+
+async fn subreddit_page(
+    state: &AppState,
+    subreddit: &str,
+    uri: &Uri,
+) -> Result<String, StatusCode> {
+    let policy = state.nsfw_policy;
+
+    if policy.disabled
+        && Subscriptions::can_subscribe(subreddit)
+        && state
+            .nsfw_subreddits
+            .is_nsfw(&state.client, subreddit, USER_AGENT)
+            .await
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let prefs = Preferences::default();
+    let fetch = |page: PageRequest| {
+        let prefs = &prefs;
+        async move {
+            crate::api::subreddit(
+                &state.client,
+                subreddit,
+                None,
+                None,
+                &page,
+                prefs,
+                USER_AGENT,
+            )
+            .await
+        }
+    };
+
+    let mut data = fetch(PageRequest::from_query(uri.query())).await?;
+    ContentFilter::server(state.blocklist.clone(), policy)
+        .apply(&mut data, fetch)
+        .await;
+
+    SubredditPage {
+        subreddit: subreddit.to_string(),
+        data,
+        uri: uri.clone(),
+    }
+    .render_page()
+}
+
+#[derive(Template)]
+#[template(path = "gemini/comments.gmi", escape = "none")]
+struct CommentsPage {
+    subreddit: String,
+    data: CommentsQuery,
+}
+
+impl CommentsPage {
+    /// Shows how deep a comment is, as Gemini can't indent
+    fn depth_marker(&self, depth: &usize) -> String {
+        "» ".repeat(*depth)
+    }
+}
+
+async fn comments_page(
+    state: &AppState,
+    subreddit: &str,
+    id: &str,
+    uri: &Uri,
+) -> Result<String, StatusCode> {
+    let prefs = Preferences::default();
+    let page = PageRequest::from_query(uri.query());
+    let data = crate::api::comments(
+        &state.client,
+        subreddit,
+        id,
+        None,
+        None,
+        &page,
+        &prefs,
+        USER_AGENT,
+    )
+    .await?;
+
+    if data.post.over_18 && state.nsfw_policy.disabled {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    CommentsPage {
+        subreddit: subreddit.to_string(),
+        data,
+    }
+    .render_page()
+}
+
+#[derive(Template)]
+#[template(path = "gemini/user.gmi", escape = "none")]
+struct UserPage {
+    username: String,
+    data: UserQuery,
+    uri: Uri,
+}
+
+async fn user_page(state: &AppState, username: &str, uri: &Uri) -> Result<String, StatusCode> {
+    let fetch = |page: PageRequest| async move {
+        crate::api::user(&state.client, username, None, None, None, &page, USER_AGENT).await
+    };
+
+    let mut data = fetch(PageRequest::from_query(uri.query())).await?;
+    ContentFilter::server(state.blocklist.clone(), state.nsfw_policy)
+        .apply(&mut data, fetch)
+        .await;
+
+    UserPage {
+        username: username.to_string(),
+        data,
+        uri: uri.clone(),
+    }
+    .render_page()
+}
+
+#[derive(Template)]
+#[template(path = "gemini/wiki.gmi", escape = "none")]
+struct WikiPage {
+    subreddit: String,
+    page: String,
+    data: WikiPageData,
+}
+
+async fn wiki_page(state: &AppState, subreddit: &str, page: &str) -> Result<String, StatusCode> {
+    let data = crate::api::wiki(&state.client, subreddit, Some(page), None, USER_AGENT).await?;
+
+    WikiPage {
+        subreddit: subreddit.to_string(),
+        page: page.to_string(),
+        data,
+    }
+    .render_page()
+}
+
+#[derive(Template)]
+#[template(path = "gemini/wiki_pages.gmi", escape = "none")]
+struct WikiPagesPage {
+    subreddit: String,
+    pages: Vec<String>,
+}
+
+async fn wiki_pages_page(state: &AppState, subreddit: &str) -> Result<String, StatusCode> {
+    let data = crate::api::wiki_pages(&state.client, subreddit, USER_AGENT).await?;
+
+    WikiPagesPage {
+        subreddit: subreddit.to_string(),
+        pages: data.0,
+    }
+    .render_page()
+}
+
+/// Images hosted by Reddit, like the HTTP `/i/` proxy.
+async fn image(state: &AppState, file: &str) -> Response {
+    let url = crate::image_proxy::reddit_image_url(file);
+
+    match crate::image_proxy::fetch(&state.client, &url, USER_AGENT).await {
+        Ok((mime, bytes)) => Response {
+            status: 20,
+            meta: mime.unwrap_or_else(|| "application/octet-stream".to_string()),
+            body: bytes.to_vec(),
+        },
+        Err(e) => e.into(),
+    }
+}
+
+mod filters {
+    /// Reddit markdown to gemtext.
+    pub fn gemtext<T: std::fmt::Display>(markdown: T) -> askama::Result<String> {
+        Ok(crate::gemtext::from_markdown(&markdown.to_string()))
+    }
+}
@@ -0,0 +1,278 @@
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag};
+
+/// Converts Reddit markdown to gemtext.
+/// Gemtext has no inline links, so they are listed as link lines after their paragraph.
+pub fn from_markdown(markdown: &str) -> String {
+    render(markdown, Writer::default())
+}
+
+/// Converts Reddit markdown to plain text, for Gopher documents and text exports.
+/// Laid out like gemtext, but links stay where they are as `label <url>`.
+pub fn plain_text(markdown: &str) -> String {
+    let writer = Writer {
+        plain: true,
+        ..Writer::default()
+    };
+    render(markdown, writer)
+}
+
+fn render(markdown: &str, mut writer: Writer) -> String {
+    let markdown = crate::utils::rewrite_reddit_links(markdown);
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+
+    for event in Parser::new_ext(&markdown, options) {
+        writer.event(event);
+    }
+
+    writer.flush();
+    writer.out.trim_end().to_string()
+}
+
+#[derive(Default)]
+struct Writer {
+    out: String,
+    /// Text of the block being read
+    line: String,
+    /// `(url, label)` of the links in the current block
+    links: Vec<(String, String)>,
+    /// Where the label of the current link starts in `line`
+    link_start: Option<usize>,
+    /// Prefix of the current block, like `* ` or `## `
+    prefix: String,
+    quote_depth: usize,
+    /// Next number of each list being read, `None` for bullet lists
+    lists: Vec<Option<u64>>,
+    in_code: bool,
+    /// Plain text instead of gemtext
+    plain: bool,
+}
+
+impl Writer {
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) if self.in_code => self.out.push_str(&text),
+            Event::Text(text) | Event::Code(text) => self.line.push_str(&text),
+            Event::SoftBreak => self.line.push(' '),
+            Event::HardBreak => self.line.push('\n'),
+            Event::Rule => {
+                self.flush();
+                self.out.push_str("-----\n\n");
+            }
+            Event::Html(_) | Event::FootnoteReference(_) | Event::TaskListMarker(_) => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading(level, _, _) => {
+                self.flush();
+                self.prefix = match level {
+                    HeadingLevel::H1 => "# ",
+                    HeadingLevel::H2 => "## ",
+                    _ => "### ",
+                }
+                .to_string();
+            }
+            Tag::List(start) => {
+                self.flush();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush();
+                // gemtext only has bullets, numbers stay text
+                self.prefix = match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "* ".to_string(),
+                };
+            }
+            Tag::BlockQuote => {
+                self.flush();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush();
+                self.in_code = true;
+                match kind {
+                    CodeBlockKind::Fenced(lang) => self.out.push_str(&format!("```{}\n", lang)),
+                    CodeBlockKind::Indented => self.out.push_str("```\n"),
+                }
+            }
+            Tag::TableCell if !self.line.is_empty() => self.line.push_str(" | "),
+            Tag::Link(_, _, _) | Tag::Image(_, _, _) => self.link_start = Some(self.line.len()),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: Tag) {
+        match tag {
+            // list items are kept together
+            Tag::Paragraph if !self.lists.is_empty() => self.flush(),
+            Tag::Paragraph | Tag::Heading(_, _, _) => {
+                self.flush();
+                self.out.push('\n');
+            }
+            Tag::Item | Tag::TableHead | Tag::TableRow => self.flush(),
+            Tag::List(_) => {
+                self.flush();
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.out.push('\n');
+                }
+            }
+            Tag::Table(_) => {
+                self.flush();
+                self.out.push('\n');
+            }
+            Tag::BlockQuote => {
+                self.flush();
+                self.quote_depth -= 1;
+            }
+            Tag::CodeBlock(_) => {
+                self.in_code = false;
+                if !self.out.ends_with('\n') {
+                    self.out.push('\n');
+                }
+                self.out.push_str("```\n\n");
+            }
+            Tag::Link(_, url, _) => self.link(url.to_string(), ""),
+            Tag::Image(_, url, _) => self.link(url.to_string(), "image: "),
+            _ => {}
+        }
+    }
+
+    fn link(&mut self, url: String, kind: &str) {
+        let start = self.link_start.take().unwrap_or(self.line.len());
+        let label = self.line[start..].trim();
+
+        if self.plain {
+            let link = match label {
+                "" => link_url(&url),
+                // autolinks are their own label
+                label if label == url => String::new(),
+                _ => format!(" <{}>", link_url(&url)),
+            };
+            self.line.insert_str(start, kind);
+            self.line.push_str(&link);
+            return;
+        }
+
+        // a link line ends at the first newline, the label has to fit on it
+        let label = label.split_whitespace().collect::<Vec<_>>().join(" ");
+        let url = link_url(&url);
+
+        let label = if label.is_empty() { url.clone() } else { label };
+        self.links.push((url, format!("{}{}", kind, label)));
+    }
+
+    /// Writes out the current block and its links.
+    fn flush(&mut self) {
+        let quote = "> ".repeat(self.quote_depth);
+        let prefix = std::mem::take(&mut self.prefix);
+
+        let text = std::mem::take(&mut self.line);
+        let text = text.trim();
+
+        if !text.is_empty() {
+            for (i, line) in text.lines().enumerate() {
+                // continued lines aren't list items or headings
+                let prefix = if i == 0 { prefix.as_str() } else { "" };
+                let line = line.trim();
+                // text that would read as gemtext, like `=> fake link`, is kept text by a space
+                let escape =
+                    if !self.plain && quote.is_empty() && prefix.is_empty() && is_markup(line) {
+                        " "
+                    } else {
+                        ""
+                    };
+                self.out
+                    .push_str(&format!("{}{}{}{}\n", quote, prefix, escape, line));
+            }
+        }
+
+        for (url, label) in std::mem::take(&mut self.links) {
+            self.out.push_str(&format!("=> {} {}\n", url, label));
+        }
+    }
+}
+
+/// The URL of a link line ends at the first space, so spaces and control characters are encoded.
+fn link_url(url: &str) -> String {
+    let mut encoded = String::with_capacity(url.len());
+
+    for c in url.chars() {
+        if c == ' ' || c.is_control() {
+            let mut bytes = [0; 4];
+            for b in c.encode_utf8(&mut bytes).bytes() {
+                encoded.push_str(&format!("%{:02X}", b));
+            }
+        } else {
+            encoded.push(c);
+        }
+    }
+
+    encoded
+}
+
+/// Whether a line starts like a gemtext link, heading, list item, quote or preformatted toggle.
+fn is_markup(line: &str) -> bool {
+    ["=>", "#", "*", ">", "```"]
+        .iter()
+        .any(|m| line.starts_with(m))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_never_becomes_markup() {
+        let gemtext =
+            from_markdown("=> gemini://evil.example fake link\n\n\\# not a heading\n\n\\`\\`\\`");
+        assert_eq!(
+            gemtext,
+            " => gemini://evil.example fake link\n\n # not a heading\n\n ```"
+        );
+
+        let gemtext = from_markdown("first line  \n\\> second line");
+        assert_eq!(gemtext, "first line\n > second line");
+    }
+
+    #[test]
+    fn link_lines_stay_one_line() {
+        let gemtext = from_markdown("[a  \n=> gemini://evil.example x](https://example.com)");
+        assert_eq!(
+            gemtext,
+            "a\n => gemini://evil.example x\n=> https://example.com a => gemini://evil.example x"
+        );
+
+        let gemtext = from_markdown("[spaced](<https://example.com/a b\tc>)");
+        assert_eq!(gemtext, "spaced\n=> https://example.com/a%20b%09c spaced");
+    }
+
+    #[test]
+    fn plain_text_keeps_links_in_place() {
+        let text = plain_text(
+            "=> not a link, see [the notes](<https://example.com/a b>) \
+             or <https://example.com>\n\n![](https://example.com/i.png)",
+        );
+        assert_eq!(
+            text,
+            "=> not a link, see the notes <https://example.com/a%20b> or https://example.com\n\n\
+             image: https://example.com/i.png"
+        );
+    }
+
+    #[test]
+    fn markup_is_kept() {
+        let gemtext = from_markdown("# Title\n\n* item\n\n> quote\n\n[link](https://example.com)");
+        assert_eq!(
+            gemtext,
+            "# Title\n\n* item\n\n> quote\n\nlink\n=> https://example.com link"
+        );
+    }
+}
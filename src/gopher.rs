@@ -0,0 +1,612 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use axum::http::{StatusCode, Uri};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::{
+    api::{CommentsQuery, SubredditQuery},
+    api_result_types::RedditData,
+    filters::ContentFilter,
+    pagination::PageRequest,
+    preferences::Preferences,
+    state::AppState,
+    subscriptions::Subscriptions,
+};
+
+/// Environment variable with the address to serve Gopher on, e.g. `0.0.0.0:70`.
+/// Gopher is off without it.
+const ADDR_VAR: &str = "OLDER_REDDIT_GOPHER";
+
+/// Environment variable with the hostname menus point clients to, `localhost` by default.
+const HOST_VAR: &str = "OLDER_REDDIT_GOPHER_HOST";
+
+/// Gopher clients don't send one, so Reddit gets ours.
+const USER_AGENT: &str = concat!("older-reddit/", env!("CARGO_PKG_VERSION"), " (gopher)");
+
+/// Longest selector and search string we read, without the CRLF.
+const MAX_REQUEST_LEN: usize = 1024;
+
+/// Time clients get to send their selector.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Columns text documents are wrapped at, leaving room for menus drawn by clients.
+const WRAP_WIDTH: usize = 70;
+
+/// Columns each level of replies is indented by.
+const INDENT_WIDTH: usize = 2;
+
+/// Replies deeper than this aren't indented any further, so they stay readable.
+const MAX_INDENT_DEPTH: usize = 8;
+
+/// Where menu items point: our host and the port we're listening on.
+#[derive(Clone)]
+struct Server {
+    host: String,
+    port: u16,
+}
+
+/// Serves Gopher if it's configured, returns right away otherwise.
+pub async fn serve(state: AppState) -> anyhow::Result<()> {
+    let Ok(addr) = std::env::var(ADDR_VAR) else {
+        return Ok(());
+    };
+
+    let listener = TcpListener::bind(&addr).await?;
+    let server = Server {
+        host: std::env::var(HOST_VAR).unwrap_or_else(|_| "localhost".to_string()),
+        port: listener.local_addr()?.port(),
+    };
+    tracing::info!("Serving Gopher on {}...", listener.local_addr()?);
+
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(c) => c,
+            Err(e) => {
+                tracing::error!("Failed to accept a Gopher connection: {}", e);
+                continue;
+            }
+        };
+
+        let (server, state) = (server.clone(), state.clone());
+
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &server, &state).await {
+                tracing::debug!("Gopher connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream, server: &Server, state: &AppState) -> anyhow::Result<()> {
+    let request = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream))
+        .await
+        .map_err(|_| anyhow!("timed out"))?;
+
+    let response = match request {
+        Ok(request) => respond(&request, server, state).await,
+        Err(e) => Menu::error(server, &e.to_string()),
+    };
+
+    stream.write_all(&response).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+/// Reads the request line: a selector, then a tab and the search string for type 7 items.
+async fn read_request(stream: &mut (impl AsyncRead + Unpin)) -> anyhow::Result<String> {
+    let mut request = Vec::new();
+    let mut buf = [0; 256];
+
+    loop {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            bail!("Connection closed before the end of the request");
+        }
+
+        request.extend_from_slice(&buf[..n]);
+
+        // some clients only send LF
+        if let Some(end) = request.iter().position(|b| *b == b'\n') {
+            request.truncate(end);
+            if request.last() == Some(&b'\r') {
+                request.pop();
+            }
+            return Ok(String::from_utf8(request)?);
+        }
+
+        if request.len() > MAX_REQUEST_LEN + 2 {
+            bail!("Request too long");
+        }
+    }
+}
+
+/// Routes a selector, mirroring the HTTP routes.
+async fn respond(request: &str, server: &Server, state: &AppState) -> Vec<u8> {
+    let (selector, input) = match request.split_once('\t') {
+        Some((selector, input)) => (selector, Some(input.trim())),
+        None => (request, None),
+    };
+
+    // some clients ask for the root menu with `/`, others with nothing
+    let selector = if selector.is_empty() { "/" } else { selector };
+    let Ok(uri) = selector.parse::<Uri>() else {
+        return Menu::error(server, "Bad selector");
+    };
+
+    let segments: Vec<&str> = uri.path().split('/').filter(|s| !s.is_empty()).collect();
+
+    let response = match segments.as_slice() {
+        [] => Ok(home(server)),
+        ["go"] => go(server, state, input).await,
+        ["search"] => search_menu(server, state, "all", &uri, input).await,
+        ["r", subreddit] => subreddit_menu(server, state, subreddit, &uri).await,
+        ["r", subreddit, "search"] => search_menu(server, state, subreddit, &uri, input).await,
+        ["r", subreddit, "comments", id, ..] => thread(state, subreddit, id, &uri).await,
+        ["u", username] | ["user", username] => user_menu(server, state, username, &uri).await,
+        ["i", file] => image(state, file).await,
+        _ => Err(StatusCode::NOT_FOUND),
+    };
+
+    match response {
+        Ok(bytes) => bytes,
+        Err(e) => Menu::error(server, error_message(e)),
+    }
+}
+
+/// Opens the subreddit typed in a type 7 item.
+async fn go(server: &Server, state: &AppState, input: Option<&str>) -> Result<Vec<u8>, StatusCode> {
+    let name = input.unwrap_or_default().trim_start_matches("r/");
+    if name.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !crate::utils::is_subreddit_name(name) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let uri = format!("/r/{}", name)
+        .parse()
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    subreddit_menu(server, state, name, &uri).await
+}
+
+fn error_message(status: StatusCode) -> &'static str {
+    match status {
+        StatusCode::NOT_FOUND => "Not found",
+        StatusCode::FORBIDDEN => "Not available",
+        StatusCode::BAD_REQUEST => "Nothing was searched for",
+        StatusCode::INTERNAL_SERVER_ERROR => "Something went wrong",
+        _ => "Reddit couldn't be reached",
+    }
+}
+
+/// A Gopher menu, one item per line, see RFC 1436.
+struct Menu<'a> {
+    server: &'a Server,
+    out: String,
+}
+
+impl<'a> Menu<'a> {
+    fn new(server: &'a Server) -> Menu<'a> {
+        Menu {
+            server,
+            out: String::new(),
+        }
+    }
+
+    /// A menu with nothing but an error line.
+    fn error(server: &Server, message: &str) -> Vec<u8> {
+        let mut menu = Menu::new(server);
+        menu.item('3', message, "");
+        menu.into_bytes()
+    }
+
+    /// Text that doesn't lead anywhere.
+    fn info(&mut self, text: &str) -> &mut Self {
+        for line in text.lines() {
+            self.item('i', line, "");
+        }
+        if text.is_empty() {
+            self.item('i', "", "");
+        }
+        self
+    }
+
+    /// An item on this server.
+    fn item(&mut self, kind: char, display: &str, selector: &str) -> &mut Self {
+        self.out.push_str(&format!(
+            "{}{}\t{}\t{}\t{}\r\n",
+            kind,
+            sanitize(display),
+            sanitize(selector),
+            self.server.host,
+            self.server.port
+        ));
+        self
+    }
+
+    /// A web link, which clients following the `URL:` convention open in a browser.
+    fn url(&mut self, display: &str, url: &str) -> &mut Self {
+        self.item('h', display, &format!("URL:{}", url))
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.out.push_str(".\r\n");
+        self.out.into_bytes()
+    }
+}
+
+/// Tabs and line breaks would end a menu field early.
+fn sanitize(field: &str) -> String {
+    field
+        .chars()
+        .map(|c| {
+            if matches!(c, '\t' | '\r' | '\n') {
+                ' '
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+fn home(server: &Server) -> Vec<u8> {
+    let mut menu = Menu::new(server);
+
+    menu.info("older-reddit")
+        .info("")
+        .item('1', "r/popular", "/r/popular")
+        .item('1', "r/all", "/r/all")
+        .item('7', "Go to a subreddit", "/go")
+        .item('7', "Search Reddit", "/search");
+
+    menu.into_bytes()
+}
+
+async fn subreddit_menu(
+    server: &Server,
+    state: &AppState,
+    subreddit: &str,
+    uri: &Uri,
+) -> Result<Vec<u8>, StatusCode> {
+    let policy = state.nsfw_policy;
+
+    if policy.disabled
+        && Subscriptions::can_subscribe(subreddit)
+        && state
+            .nsfw_subreddits
+            .is_nsfw(&state.client, subreddit, USER_AGENT)
+            .await
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let prefs = Preferences::default();
+    let fetch = |page: PageRequest| {
+        let prefs = &prefs;
+        async move {
+            crate::api::subreddit(
+                &state.client,
+                subreddit,
+                None,
+                None,
+                &page,
+                prefs,
+                USER_AGENT,
+            )
+            .await
+        }
+    };
+
+    let mut data = fetch(PageRequest::from_query(uri.query())).await?;
+    ContentFilter::server(state.blocklist.clone(), policy)
+        .apply(&mut data, fetch)
+        .await;
+
+    let mut menu = Menu::new(server);
+    menu.info(&format!("r/{}", subreddit)).info("");
+    posts(&mut menu, &data, uri);
+    menu.item(
+        '7',
+        &format!("Search r/{}", subreddit),
+        &format!("/r/{}/search", subreddit),
+    );
+
+    Ok(menu.into_bytes())
+}
+
+/// Searches what's typed in a type 7 item, or the `q` of a following page.
+async fn search_menu(
+    server: &Server,
+    state: &AppState,
+    subreddit: &str,
+    uri: &Uri,
+    input: Option<&str>,
+) -> Result<Vec<u8>, StatusCode> {
+    let page = PageRequest::from_query(uri.query());
+    let query = match input {
+        Some(q) => q.to_string(),
+        None => uri
+            .query()
+            .and_then(|q| {
+                serde_urlencoded::from_str::<Vec<(String, String)>>(q)
+                    .ok()?
+                    .into_iter()
+                    .find(|(k, _)| k == "q")
+            })
+            .map(|(_, q)| q)
+            .unwrap_or_default(),
+    };
+
+    if query.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // the query goes in the selector, so the following pages keep it
+    let uri: Uri = format!(
+        "{}?{}",
+        uri.path(),
+        serde_urlencoded::to_string([("q", &query)]).map_err(|_| StatusCode::BAD_REQUEST)?
+    )
+    .parse()
+    .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let prefs = Preferences::default();
+    let fetch = |page: PageRequest| {
+        let (prefs, query) = (&prefs, &query);
+        async move {
+            crate::api::search(
+                &state.client,
+                subreddit,
+                query,
+                None,
+                None,
+                &page,
+                !state.nsfw_policy.disabled,
+                subreddit != "all",
+                prefs,
+                USER_AGENT,
+            )
+            .await
+        }
+    };
+
+    let mut data = fetch(page).await?;
+    ContentFilter::server(state.blocklist.clone(), state.nsfw_policy)
+        .apply(&mut data, fetch)
+        .await;
+
+    let mut menu = Menu::new(server);
+    menu.info(&format!("Results for \"{}\" in r/{}", query, subreddit))
+        .info("");
+    posts(&mut menu, &data, &uri);
+
+    Ok(menu.into_bytes())
+}
+
+/// A listing's posts, with their media and the following page.
+fn posts(menu: &mut Menu, data: &SubredditQuery, uri: &Uri) {
+    for post in &data.posts {
+        let mut title = post.title.clone();
+        if post.over_18 {
+            title.push_str(" [NSFW]");
+        }
+        if post.spoiler {
+            title.push_str(" [spoiler]");
+        }
+
+        menu.item(
+            '0',
+            &title,
+            &format!("/r/{}/comments/{}", post.subreddit, post.id),
+        );
+        menu.info(&format!(
+            "  {} points - u/{} in r/{} - {} comments",
+            post.score, post.author, post.subreddit, post.num_comments
+        ));
+
+        if let Some(gallery) = &post.gallery_data {
+            for (i, item) in gallery.items.iter().enumerate() {
+                menu.item(
+                    'I',
+                    &format!("  Image {}", i + 1),
+                    &format!("/i/{}.jpg", item.media_id),
+                );
+            }
+        } else if let Some(url) = &post.url {
+            if let Some(file) = url.strip_prefix("https://i.redd.it/") {
+                menu.item('I', "  Image", &format!("/i/{}", file));
+            } else if !post.domain.starts_with("self.") && !post.is_video {
+                menu.url(&format!("  {}", post.domain), url);
+            }
+        }
+    }
+
+    if data.hidden > 0 {
+        menu.info("")
+            .info(&format!("{} hidden by the server's filters", data.hidden));
+    }
+
+    if let Some(next) = data.pagination.after_url(uri) {
+        menu.info("").item('1', "Next page", &next);
+    }
+}
+
+async fn user_menu(
+    server: &Server,
+    state: &AppState,
+    username: &str,
+    uri: &Uri,
+) -> Result<Vec<u8>, StatusCode> {
+    let fetch = |page: PageRequest| async move {
+        crate::api::user(&state.client, username, None, None, None, &page, USER_AGENT).await
+    };
+
+    let mut data = fetch(PageRequest::from_query(uri.query())).await?;
+    ContentFilter::server(state.blocklist.clone(), state.nsfw_policy)
+        .apply(&mut data, fetch)
+        .await;
+
+    let mut menu = Menu::new(server);
+    menu.info(&format!("u/{}", username)).info("");
+
+    for child in &data.children {
+        match child {
+            RedditData::T3(post) => {
+                menu.item(
+                    '0',
+                    &post.title,
+                    &format!("/r/{}/comments/{}", post.subreddit, post.id),
+                );
+                menu.info(&format!(
+                    "  {} points in r/{} - {} comments",
+                    post.score, post.subreddit, post.num_comments
+                ));
+            }
+            RedditData::T1(comment) => {
+                // the post's id is the `t3_` fullname without its prefix
+                let post_id = comment.link_id.trim_start_matches("t3_");
+                let title = comment.link_title.as_deref().unwrap_or("a post");
+
+                menu.item(
+                    '0',
+                    &format!("Comment on {}", title),
+                    &format!("/r/{}/comments/{}", comment.subreddit, post_id),
+                );
+                menu.info(&format!(
+                    "  {} points in r/{}",
+                    comment.score, comment.subreddit
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(next) = data.pagination.after_url(uri) {
+        menu.info("").item('1', "Next page", &next);
+    }
+
+    Ok(menu.into_bytes())
+}
+
+/// A post and its comments as a text document, replies indented under their parent.
+async fn thread(
+    state: &AppState,
+    subreddit: &str,
+    id: &str,
+    uri: &Uri,
+) -> Result<Vec<u8>, StatusCode> {
+    let prefs = Preferences::default();
+    let page = PageRequest::from_query(uri.query());
+    let data = crate::api::comments(
+        &state.client,
+        subreddit,
+        id,
+        None,
+        None,
+        &page,
+        &prefs,
+        USER_AGENT,
+    )
+    .await?;
+
+    if data.post.over_18 && state.nsfw_policy.disabled {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let mut doc = Document::default();
+    write_post(&mut doc, &data);
+
+    for (depth, comment) in data.thread() {
+        let indent = " ".repeat(depth.min(MAX_INDENT_DEPTH) * INDENT_WIDTH);
+
+        let mut byline = format!("u/{} - {} points", comment.author, comment.score);
+        if comment.is_submitter {
+            byline.push_str(" - OP");
+        }
+
+        doc.text(&indent, &byline);
+        doc.text(&indent, &crate::gemtext::plain_text(&comment.body));
+        doc.blank();
+    }
+
+    Ok(doc.into_bytes())
+}
+
+fn write_post(doc: &mut Document, data: &CommentsQuery) {
+    let post = &data.post;
+
+    doc.text("", &post.title);
+    doc.text(
+        "",
+        &format!(
+            "{} points - u/{} in r/{} - {} comments",
+            post.score, post.author, post.subreddit, post.num_comments
+        ),
+    );
+    if post.over_18 {
+        doc.text("", "NSFW");
+    }
+    doc.blank();
+
+    if let Some(url) = data.get_url() {
+        if !post.domain.starts_with("self.") {
+            doc.text("", &url);
+            doc.blank();
+        }
+    }
+
+    if !post.selftext.is_empty() {
+        doc.text("", &crate::gemtext::plain_text(&post.selftext));
+        doc.blank();
+    }
+
+    doc.text("", &"-".repeat(WRAP_WIDTH));
+    doc.blank();
+}
+
+/// A type 0 text document, with CRLF line endings and a lone `.` at the end.
+#[derive(Default)]
+struct Document {
+    lines: Vec<String>,
+}
+
+impl Document {
+    /// Adds `text` wrapped to [`WRAP_WIDTH`], each line starting with `indent`.
+    fn text(&mut self, indent: &str, text: &str) {
+        self.lines
+            .extend(crate::utils::wrap_text(text, indent, WRAP_WIDTH));
+    }
+
+    fn blank(&mut self) {
+        self.lines.push(String::new());
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut out = String::new();
+
+        for line in self.lines {
+            // a lone `.` would end the document early
+            if line.starts_with('.') {
+                out.push('.');
+            }
+            out.push_str(line.trim_end());
+            out.push_str("\r\n");
+        }
+
+        out.push_str(".\r\n");
+        out.into_bytes()
+    }
+}
+
+/// Images hosted by Reddit, like the HTTP `/i/` proxy, sent as they are.
+async fn image(state: &AppState, file: &str) -> Result<Vec<u8>, StatusCode> {
+    let url = crate::image_proxy::reddit_image_url(file);
+    let (_, bytes) = crate::image_proxy::fetch(&state.client, &url, USER_AGENT).await?;
+
+    Ok(bytes.to_vec())
+}
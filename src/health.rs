@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+
+use crate::{
+    nsfw::{NsfwSubreddits, MAX_CACHED_SUBREDDITS},
+    share::{ShareLinks, MAX_CACHED_LINKS},
+    upstream::{Client, RateLimit},
+};
+
+/// How old Reddit's last answer may be before `/readyz` asks again.
+/// Answers to users count, so a busy server never probes.
+const PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Sent with probes, which aren't made for any user.
+const PROBE_USER_AGENT: &str = "older-reddit readiness probe";
+
+#[derive(Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Ok,
+    /// Reddit answers, but with 429s or errors
+    Degraded,
+    /// Reddit doesn't answer at all
+    Unavailable,
+}
+
+#[derive(Serialize)]
+pub struct Readiness {
+    status: Status,
+    upstream: UpstreamReport,
+    ratelimit: Option<RateLimit>,
+    caches: CachesReport,
+}
+
+#[derive(Serialize)]
+struct UpstreamReport {
+    /// Served from a snapshot store, Reddit is never asked
+    offline: bool,
+    reachable: bool,
+    /// Of Reddit's last answer
+    last_status: Option<u16>,
+    /// Age of Reddit's last answer
+    checked_seconds_ago: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct CachesReport {
+    share_links: CacheReport,
+    nsfw_subreddits: CacheReport,
+}
+
+#[derive(Serialize)]
+struct CacheReport {
+    entries: usize,
+    capacity: usize,
+}
+
+/// `/healthz`, answered as long as the process is.
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+/// `/readyz`, whether Reddit can be reached and how it's answering.
+/// 503 when it can't be reached, degraded (but 200) when it's rate limiting us or failing.
+pub async fn readyz(
+    State(client): State<Client>,
+    State(share_links): State<ShareLinks>,
+    State(nsfw_subreddits): State<NsfwSubreddits>,
+) -> (StatusCode, Json<Readiness>) {
+    let caches = CachesReport {
+        share_links: CacheReport {
+            entries: share_links.cached().await,
+            capacity: MAX_CACHED_LINKS,
+        },
+        nsfw_subreddits: CacheReport {
+            entries: nsfw_subreddits.cached().await,
+            capacity: MAX_CACHED_SUBREDDITS,
+        },
+    };
+
+    if client.is_offline() {
+        let readiness = Readiness {
+            status: Status::Ok,
+            upstream: UpstreamReport {
+                offline: true,
+                reachable: true,
+                last_status: None,
+                checked_seconds_ago: None,
+            },
+            ratelimit: None,
+            caches,
+        };
+        return (StatusCode::OK, Json(readiness));
+    }
+
+    let fresh = client
+        .last_answer()
+        .is_some_and(|a| a.at.elapsed() < PROBE_INTERVAL);
+    if !fresh {
+        probe(&client).await;
+    }
+
+    let answer = client.last_answer();
+    let last_status = answer.and_then(|a| a.status);
+    let ratelimit = answer.and_then(|a| a.ratelimit);
+
+    let status = match last_status {
+        None => Status::Unavailable,
+        Some(s) if s == StatusCode::TOO_MANY_REQUESTS || s.is_server_error() => Status::Degraded,
+        Some(_) if ratelimit.is_some_and(|r| r.remaining < 1.0) => Status::Degraded,
+        Some(_) => Status::Ok,
+    };
+
+    let code = if status == Status::Unavailable {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    };
+
+    let readiness = Readiness {
+        status,
+        upstream: UpstreamReport {
+            offline: false,
+            reachable: last_status.is_some(),
+            last_status: last_status.map(|s| s.as_u16()),
+            checked_seconds_ago: answer.map(|a| a.at.elapsed().as_secs()),
+        },
+        ratelimit,
+        caches,
+    };
+
+    (code, Json(readiness))
+}
+
+/// About the smallest thing Reddit's API serves.
+/// Any answer, even a 404, shows Reddit is reachable: only 429s and errors count against it,
+/// the client records them.
+async fn probe(client: &Client) {
+    let mut url = crate::utils::get_reddit_domain();
+    url.add_route("r");
+    url.add_route("reddit");
+    url.add_route("about.json");
+
+    if let Err(e) = client.get(&url.build(), PROBE_USER_AGENT).await {
+        tracing::warn!("Readiness probe failed: {}", e);
+    }
+}
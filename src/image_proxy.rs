@@ -5,7 +5,8 @@ use axum::{
     TypedHeader,
 };
 use bytes::Bytes;
-use reqwest::{header::USER_AGENT, Client};
+
+use crate::upstream::Client;
 
 #[axum::debug_handler]
 pub async fn reddit_image_proxy(
@@ -13,17 +14,86 @@ pub async fn reddit_image_proxy(
     TypedHeader(user_agent): TypedHeader<UserAgent>,
     State(client): State<Client>,
 ) -> Result<Response<axum::body::Full<Bytes>>, StatusCode> {
-    let url = format!("https://i.redd.it/{}", file);
-    match client
-        .get(&url)
-        .header(USER_AGENT, user_agent.as_str())
-        .send()
-        .await
-    {
+    proxy(
+        &client,
+        "image",
+        &reddit_image_url(&file),
+        user_agent.as_str(),
+    )
+    .await
+}
+
+/// Listing thumbnails, see [`crate::api_result_types::T3Data::thumbnail_url`]
+pub async fn reddit_thumbnail_proxy(
+    Path(file): Path<String>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+) -> Result<Response<axum::body::Full<Bytes>>, StatusCode> {
+    proxy(
+        &client,
+        "thumbnail",
+        &reddit_thumbnail_url(&file),
+        user_agent.as_str(),
+    )
+    .await
+}
+
+/// Where Reddit hosts the images served under `/i/`.
+pub fn reddit_image_url(file: &str) -> String {
+    format!("https://i.redd.it/{}", file)
+}
+
+/// Where Reddit hosts the thumbnails served under `/t/`.
+pub fn reddit_thumbnail_url(file: &str) -> String {
+    format!("https://b.thumbs.redditmedia.com/{}", file)
+}
+
+/// Where archives keep an image from Reddit, `i/<file>` or `t/<file>` like the routes.
+pub fn media_file(url: &str) -> Option<String> {
+    let (dir, file) = if let Some(f) = url.strip_prefix("https://i.redd.it/") {
+        ("i", f)
+    } else if let Some(f) = url.strip_prefix("https://b.thumbs.redditmedia.com/") {
+        ("t", f)
+    } else {
+        return None;
+    };
+
+    is_safe_file(file).then(|| format!("{}/{}", dir, file))
+}
+
+/// Whether a file name from Reddit can be used as it is in a path or a URL:
+/// no directories, queries or hidden files.
+pub fn is_safe_file(file: &str) -> bool {
+    !file.starts_with('.')
+        && !file.is_empty()
+        && file
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+/// `kind` is what the bytes served are counted as in the metrics.
+async fn proxy(
+    client: &Client,
+    kind: &str,
+    url: &str,
+    user_agent: &str,
+) -> Result<Response<axum::body::Full<Bytes>>, StatusCode> {
+    let (_, image_bytes) = fetch(client, url, user_agent).await?;
+    crate::metrics::media_served(kind, image_bytes.len());
+    Ok(Response::new(axum::body::Full::new(image_bytes)))
+}
+
+/// Downloads an image, with its content type if Reddit sent one.
+pub async fn fetch(
+    client: &Client,
+    url: &str,
+    user_agent: &str,
+) -> Result<(Option<String>, Bytes), StatusCode> {
+    match client.get(url, user_agent).await {
         Ok(response) => {
             if response.status().is_success() {
-                let image_bytes = response.bytes().await.unwrap();
-                Ok(Response::new(axum::body::Full::new(image_bytes)))
+                let content_type = response.content_type().map(str::to_string);
+                Ok((content_type, response.bytes()))
             } else {
                 Err(StatusCode::NOT_FOUND)
             }
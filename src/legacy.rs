@@ -0,0 +1,124 @@
+use std::convert::Infallible;
+
+use askama::Template;
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, OriginalUri},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use cookie::Key;
+
+use crate::preferences::Preferences;
+
+/// Path prefix serving every page with the legacy templates, e.g. `/lite/r/rust`.
+pub const PREFIX: &str = "/lite";
+
+/// Browsers that predate CSS, or never did layout, matched against the lowercase User-Agent.
+const LEGACY_BROWSERS: [&str; 10] = [
+    "lynx/", "links (", "elinks", "w3m/", "mosaic", "dillo/", "msie 2.", "msie 3.", "msie 4.",
+    "msie 5.",
+];
+
+/// Which template set a page is rendered with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Modern,
+    /// HTML 3.2 tables and `<font>`, no CSS.
+    /// `prefix` is prepended to links, so pages opened under [`PREFIX`] stay there.
+    Legacy {
+        prefix: &'static str,
+    },
+}
+
+impl Layout {
+    /// Prepended to local links, empty unless the page was opened under [`PREFIX`].
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            Layout::Modern => "",
+            Layout::Legacy { prefix } => prefix,
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Layout
+where
+    S: Send + Sync,
+    Key: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let OriginalUri(uri) = OriginalUri::from_request_parts(parts, state).await?;
+
+        let path = uri.path();
+        if path == PREFIX || path.starts_with(&format!("{}/", PREFIX)) {
+            return Ok(Layout::Legacy { prefix: PREFIX });
+        }
+
+        let prefs = Preferences::from_request_parts(parts, state).await?;
+
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|u| u.to_str().ok())
+            .unwrap_or_default();
+
+        let layout = if prefs.legacy_layout || is_legacy_browser(user_agent) {
+            Layout::Legacy { prefix: "" }
+        } else {
+            Layout::Modern
+        };
+        Ok(layout)
+    }
+}
+
+/// Netscape 4 and older say `Mozilla/4.x` without the `compatible` of later Internet Explorers.
+fn is_legacy_browser(user_agent: &str) -> bool {
+    let user_agent = user_agent.to_lowercase();
+
+    if LEGACY_BROWSERS.iter().any(|b| user_agent.contains(b)) {
+        return true;
+    }
+
+    ["mozilla/1.", "mozilla/2.", "mozilla/3.", "mozilla/4."]
+        .iter()
+        .any(|m| user_agent.starts_with(m))
+        && !user_agent.contains("compatible")
+}
+
+/// Renders a legacy template as plain ASCII, which old browsers can't get wrong.
+pub fn render(template: impl Template) -> Response {
+    match template.render() {
+        Ok(html) => (
+            [(header::CONTENT_TYPE, "text/html; charset=us-ascii")],
+            to_ascii(&html),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!("Failed to render legacy template: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Typographic punctuation becomes its ASCII look-alike, anything else a character reference.
+fn to_ascii(html: &str) -> String {
+    let mut ascii = String::with_capacity(html.len());
+
+    for c in html.chars() {
+        match c {
+            c if c.is_ascii() => ascii.push(c),
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{2032}' => ascii.push('\''),
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{2033}' => ascii.push('"'),
+            '\u{2010}'..='\u{2015}' | '\u{2212}' => ascii.push('-'),
+            '\u{2026}' => ascii.push_str("..."),
+            '\u{2022}' | '\u{00B7}' => ascii.push('*'),
+            '\u{00A0}' => ascii.push_str("&nbsp;"),
+            c => ascii.push_str(&format!("&#{};", c as u32)),
+        }
+    }
+
+    ascii
+}
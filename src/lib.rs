@@ -0,0 +1,35 @@
+pub mod api;
+pub mod api_result_types;
+pub mod api_types;
+pub mod api_v1;
+pub mod archive;
+pub mod cache;
+pub mod comments;
+pub mod cookies;
+pub mod csrf;
+pub mod export;
+pub mod feed;
+pub mod filters;
+pub mod gemini;
+pub mod gemtext;
+pub mod gopher;
+pub mod health;
+pub mod image_proxy;
+pub mod legacy;
+pub mod metrics;
+pub mod nsfw;
+pub mod pagination;
+pub mod preferences;
+pub mod routes;
+pub mod schema;
+pub mod search;
+pub mod settings;
+pub mod share;
+pub mod state;
+pub mod subreddit;
+pub mod subscriptions;
+pub mod themes;
+pub mod upstream;
+pub mod user;
+pub mod utils;
+pub mod wiki;
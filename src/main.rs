@@ -1,15 +1,4 @@
-mod api;
-mod api_result_types;
-mod api_types;
-mod comments;
-mod image_proxy;
-mod search;
-mod subreddit;
-mod utils;
-mod wiki;
-mod user;
-
-use axum::{response::Redirect, routing::get, Router};
+use older_reddit::{gemini, gopher, routes, state::AppState, upstream};
 
 #[tokio::main]
 async fn main() {
@@ -23,22 +12,26 @@ async fn main() {
 }
 
 async fn run() -> anyhow::Result<()> {
-    let app = Router::new()
-        .route("/", get(|| async { Redirect::permanent("/r/all") }))
-        .route("/r/:subreddit", get(subreddit::subreddit))
-        .route("/r/:subreddit/comments/:file", get(comments::comments))
-        .route("/r/:subreddit/search", get(search::search_handler))
-        .route("/r/:subreddit/wiki", get(wiki::wiki_page))
-        .route("/u/:username", get(user::user))
-        .route("/i/:id", get(image_proxy::reddit_image_proxy))
-        .with_state(reqwest::Client::new());
+    let client = upstream::Client::from_args(std::env::args().skip(1))?;
+
+    let state = AppState::from_env(client)?;
+
+    let gemini = gemini::serve(state.clone());
+    let gopher = gopher::serve(state.clone());
+
+    let app = routes::router(state);
 
     let listener = std::net::TcpListener::bind("0.0.0.0:3000")?;
     tracing::info!("Listening on {}...", listener.local_addr()?);
 
-    axum::Server::from_tcp(listener)?
-        .serve(app.into_make_service())
-        .await?;
+    let http = async {
+        axum::Server::from_tcp(listener)?
+            .serve(app.into_make_service())
+            .await?;
+        anyhow::Ok(())
+    };
+
+    tokio::try_join!(http, gemini, gopher)?;
 
     Ok(())
 }
@@ -0,0 +1,193 @@
+use std::{sync::OnceLock, time::Instant};
+
+use axum::{
+    extract::MatchedPath,
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus::{
+    Encoder, Gauge, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+/// Everything `/metrics` exposes, for the whole process.
+struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    upstream_requests: IntCounterVec,
+    upstream_duration: HistogramVec,
+    ratelimit_remaining: Gauge,
+    ratelimit_reset: Gauge,
+    cache_lookups: IntCounterVec,
+    media_bytes: IntCounterVec,
+    decode_failures: IntCounterVec,
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Metrics> {
+        let registry = Registry::new_custom(Some("older_reddit".to_string()), None)?;
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Requests served, by route"),
+            &["route", "method", "status"],
+        )?;
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to serve requests, by route",
+            ),
+            &["route", "method"],
+        )?;
+        let upstream_requests = IntCounterVec::new(
+            Opts::new(
+                "upstream_requests_total",
+                "Requests made to Reddit, by endpoint and status, `error` if none came back",
+            ),
+            &["endpoint", "status"],
+        )?;
+        let upstream_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "upstream_request_duration_seconds",
+                "Time Reddit took to answer, by endpoint",
+            ),
+            &["endpoint"],
+        )?;
+        let ratelimit_remaining = Gauge::new(
+            "upstream_ratelimit_remaining",
+            "Requests Reddit still allows until the rate limit resets, as of its last answer",
+        )?;
+        let ratelimit_reset = Gauge::new(
+            "upstream_ratelimit_reset_seconds",
+            "Seconds until Reddit resets the rate limit, as of its last answer",
+        )?;
+        let cache_lookups = IntCounterVec::new(
+            Opts::new("cache_lookups_total", "Cache lookups, by cache and result"),
+            &["cache", "result"],
+        )?;
+        let media_bytes = IntCounterVec::new(
+            Opts::new(
+                "media_bytes_total",
+                "Bytes of images served by the media proxy",
+            ),
+            &["kind"],
+        )?;
+        let decode_failures = IntCounterVec::new(
+            Opts::new(
+                "decode_failures_total",
+                "Responses from Reddit that couldn't be decoded, by endpoint",
+            ),
+            &["endpoint"],
+        )?;
+
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+        registry.register(Box::new(upstream_requests.clone()))?;
+        registry.register(Box::new(upstream_duration.clone()))?;
+        registry.register(Box::new(ratelimit_remaining.clone()))?;
+        registry.register(Box::new(ratelimit_reset.clone()))?;
+        registry.register(Box::new(cache_lookups.clone()))?;
+        registry.register(Box::new(media_bytes.clone()))?;
+        registry.register(Box::new(decode_failures.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            requests,
+            request_duration,
+            upstream_requests,
+            upstream_duration,
+            ratelimit_remaining,
+            ratelimit_reset,
+            cache_lookups,
+            media_bytes,
+            decode_failures,
+        })
+    }
+}
+
+fn registered() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+    // the names and labels are fixed, so this can't fail
+    METRICS.get_or_init(|| Metrics::new().expect("invalid metrics"))
+}
+
+/// Middleware counting and timing requests, by route rather than path
+/// so there is one series per page type, not one per subreddit.
+/// Applied with `route_layer`, as the route is only known once matched.
+pub async fn track<B>(request: Request<B>, next: Next<B>) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_default();
+    let method = request.method().to_string();
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+
+    let m = registered();
+    m.request_duration
+        .with_label_values(&[&route, &method])
+        .observe(start.elapsed().as_secs_f64());
+    m.requests
+        .with_label_values(&[&route, &method, response.status().as_str()])
+        .inc();
+
+    response
+}
+
+/// A request made to Reddit, `status` is `None` if it failed before an answer.
+pub fn upstream_request(endpoint: &str, status: Option<StatusCode>, start: Instant) {
+    let m = registered();
+    let status = status.as_ref().map_or("error", StatusCode::as_str);
+
+    m.upstream_requests
+        .with_label_values(&[endpoint, status])
+        .inc();
+    m.upstream_duration
+        .with_label_values(&[endpoint])
+        .observe(start.elapsed().as_secs_f64());
+}
+
+/// From Reddit's `x-ratelimit-remaining` and `x-ratelimit-reset` headers.
+pub fn ratelimit(remaining: f64, reset: f64) {
+    let m = registered();
+    m.ratelimit_remaining.set(remaining);
+    m.ratelimit_reset.set(reset);
+}
+
+pub fn cache_lookup(cache: &str, hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    registered()
+        .cache_lookups
+        .with_label_values(&[cache, result])
+        .inc();
+}
+
+pub fn media_served(kind: &str, bytes: usize) {
+    registered()
+        .media_bytes
+        .with_label_values(&[kind])
+        .inc_by(bytes as u64);
+}
+
+pub fn decode_failure(endpoint: &str) {
+    registered()
+        .decode_failures
+        .with_label_values(&[endpoint])
+        .inc();
+}
+
+/// `/metrics`, in Prometheus' text format.
+pub async fn metrics() -> Response {
+    let mut body = Vec::new();
+    let encoder = TextEncoder::new();
+
+    if let Err(e) = encoder.encode(&registered().registry.gather(), &mut body) {
+        tracing::error!("{}", e);
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    ([(CONTENT_TYPE, encoder.format_type().to_string())], body).into_response()
+}
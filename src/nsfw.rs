@@ -0,0 +1,221 @@
+use std::convert::Infallible;
+
+use askama::Template;
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, State},
+    http::{header, request::Parts, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use cookie::Key;
+use serde::Deserialize;
+
+use crate::{
+    cache::BoundedCache,
+    csrf::SameOrigin,
+    legacy::Layout,
+    preferences::Preferences,
+    themes::{CurrentTheme, Themes},
+    upstream::Client,
+};
+
+/// Environment variable turning NSFW content off for everyone when set to `1` or `true`.
+const DISABLE_VAR: &str = "OLDER_REDDIT_DISABLE_NSFW";
+
+/// Emptying the cache when it's full is also how a subreddit that changed rating gets
+/// looked up again, so it is kept small enough to fill up every so often on a busy server.
+pub const MAX_CACHED_SUBREDDITS: usize = 4096;
+
+/// Whether the server shows NSFW content at all.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NsfwPolicy {
+    pub disabled: bool,
+}
+
+impl NsfwPolicy {
+    pub fn from_env() -> NsfwPolicy {
+        let disabled = std::env::var(DISABLE_VAR)
+            .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+            .unwrap_or_default();
+
+        if disabled {
+            tracing::info!("NSFW content is disabled");
+        }
+
+        NsfwPolicy { disabled }
+    }
+}
+
+/// Which subreddits are NSFW, so their about page is fetched once.
+#[derive(Clone)]
+pub struct NsfwSubreddits {
+    cache: BoundedCache<String, bool>,
+}
+
+impl Default for NsfwSubreddits {
+    fn default() -> Self {
+        NsfwSubreddits {
+            cache: BoundedCache::new("nsfw_subreddits", MAX_CACHED_SUBREDDITS),
+        }
+    }
+}
+
+impl NsfwSubreddits {
+    /// Subreddits known so far, out of [`MAX_CACHED_SUBREDDITS`].
+    pub async fn cached(&self) -> usize {
+        self.cache.entries().await
+    }
+
+    /// Subreddits we can't get the about page of are considered safe,
+    /// their posts are still marked.
+    pub async fn is_nsfw(&self, client: &Client, subreddit: &str, user_agent: &str) -> bool {
+        let name = subreddit.to_lowercase();
+
+        if let Some(nsfw) = self.cache.get(&name).await {
+            return nsfw;
+        }
+
+        let nsfw = match crate::api::subreddit_about(client, subreddit, user_agent).await {
+            Ok(about) => about.is_nsfw(),
+            Err(_) => return false,
+        };
+
+        self.cache.insert(name, nsfw).await;
+
+        nsfw
+    }
+}
+
+/// Stands between users and NSFW content.
+pub struct NsfwGate {
+    policy: NsfwPolicy,
+    /// The user asked not to be asked again
+    consent: bool,
+    /// For the warning page
+    theme: CurrentTheme,
+    layout: Layout,
+    uri: Uri,
+}
+
+impl NsfwGate {
+    /// Whether NSFW content would be shown without asking.
+    pub fn is_open(&self) -> bool {
+        !self.policy.disabled && self.consent
+    }
+
+    /// Page to show instead of `uri` if it is NSFW: a warning, or an error if NSFW is disabled.
+    pub fn check(&self, nsfw: bool, uri: &Uri) -> Option<Response> {
+        if !nsfw || self.is_open() {
+            return None;
+        }
+
+        if self.policy.disabled {
+            return Some(self.blocked());
+        }
+
+        let back = match uri.path_and_query() {
+            Some(p) => format!("{}{}", self.layout.prefix(), p),
+            None => "/".to_string(),
+        };
+
+        Some(self.page(back, false))
+    }
+
+    /// Error for NSFW content when the server doesn't show any, `None` otherwise.
+    /// For places nobody can be asked, like feeds.
+    pub fn check_disabled(&self, nsfw: bool) -> Option<Response> {
+        (nsfw && self.policy.disabled).then(|| self.blocked())
+    }
+
+    fn blocked(&self) -> Response {
+        (StatusCode::FORBIDDEN, self.page("/".to_string(), true)).into_response()
+    }
+
+    fn page(&self, back: String, disabled: bool) -> Response {
+        if let Layout::Legacy { prefix } = self.layout {
+            return crate::legacy::render(LegacyNsfwTemplate {
+                prefix,
+                back,
+                disabled,
+                uri: self.uri.clone(),
+            });
+        }
+
+        NsfwTemplate {
+            back,
+            disabled,
+            theme: self.theme.clone(),
+        }
+        .into_response()
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for NsfwGate
+where
+    S: Send + Sync,
+    Key: FromRef<S>,
+    NsfwPolicy: FromRef<S>,
+    Themes: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let prefs = Preferences::from_request_parts(parts, state).await?;
+        let theme = CurrentTheme::from_request_parts(parts, state).await?;
+        let layout = Layout::from_request_parts(parts, state).await?;
+
+        Ok(NsfwGate {
+            policy: NsfwPolicy::from_ref(state),
+            consent: prefs.nsfw_consent,
+            theme,
+            layout,
+            uri: parts.uri.clone(),
+        })
+    }
+}
+
+#[derive(Template)]
+#[template(path = "nsfw.html")]
+pub struct NsfwTemplate {
+    /// Where the continue button leads
+    back: String,
+    disabled: bool,
+    theme: CurrentTheme,
+}
+
+#[derive(Template)]
+#[template(path = "legacy/nsfw.html")]
+pub struct LegacyNsfwTemplate {
+    prefix: &'static str,
+    back: String,
+    disabled: bool,
+    uri: Uri,
+}
+
+/// Remembers the user is fine with NSFW content and goes back to it.
+pub async fn allow_nsfw(
+    _: SameOrigin,
+    State(key): State<Key>,
+    mut prefs: Preferences,
+    Form(form): Form<AllowNsfwForm>,
+) -> Response {
+    prefs.nsfw_consent = true;
+
+    let back = form
+        .back
+        .filter(|b| crate::utils::is_local_path(b))
+        .unwrap_or_else(|| "/".to_string());
+    let redirect = Redirect::to(&back);
+
+    match prefs.to_cookie(&key) {
+        Some(c) => ([(header::SET_COOKIE, c)], redirect).into_response(),
+        None => redirect.into_response(),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AllowNsfwForm {
+    back: Option<String>,
+}
@@ -0,0 +1,184 @@
+use axum::http::Uri;
+use serde::Deserialize;
+
+/// Page size Reddit uses when no `limit` is given
+const PAGE_SIZE: usize = 25;
+
+/// Which page of a listing to show, straight from the query string.
+/// Extracted next to the handler's own parameters, with a second `Query`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PageRequest {
+    pub after: Option<String>,
+    pub before: Option<String>,
+    /// Number of items on the pages before this one
+    #[serde(default)]
+    pub count: usize,
+}
+
+impl PageRequest {
+    /// For frontends without axum's `Query`, like `?after=t3_abc&count=25`.
+    pub fn from_query(query: Option<&str>) -> PageRequest {
+        serde_urlencoded::from_str(query.unwrap_or_default()).unwrap_or_default()
+    }
+
+    /// Opaque token for this page, so the JSON API doesn't expose Reddit's fullnames.
+    pub fn token(&self) -> String {
+        let (direction, fullname) = match (&self.after, &self.before) {
+            (Some(a), _) => ('a', a.as_str()),
+            (None, Some(b)) => ('b', b.as_str()),
+            (None, None) => ('a', ""),
+        };
+
+        format!("{}{}:{}", direction, self.count, fullname)
+            .bytes()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Reads a [`PageRequest::token`] back, `None` if it isn't one.
+    pub fn from_token(token: &str) -> Option<PageRequest> {
+        let bytes = (0..token.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(token.get(i..i + 2)?, 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        let token = String::from_utf8(bytes).ok()?;
+
+        let (head, fullname) = token.split_once(':')?;
+        if !fullname.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return None;
+        }
+
+        let count = head.get(1..)?.parse().ok()?;
+        let fullname = (!fullname.is_empty()).then(|| fullname.to_string());
+
+        match head.chars().next()? {
+            'a' => Some(PageRequest {
+                after: fullname,
+                before: None,
+                count,
+            }),
+            'b' => Some(PageRequest {
+                after: None,
+                before: fullname,
+                count,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn add_params(&self, base: &mut url_builder::URLBuilder) {
+        if let Some(a) = &self.after {
+            base.add_param("after", a);
+        }
+
+        if let Some(b) = &self.before {
+            base.add_param("before", b);
+        }
+
+        // without it Reddit never tells us what comes before
+        if self.count > 0 {
+            base.add_param("count", &self.count.to_string());
+        }
+    }
+}
+
+/// Where a page sits in a listing, and how to get to the ones around it.
+#[derive(Debug, Clone, Default)]
+pub struct Pagination {
+    before: Option<String>,
+    after: Option<String>,
+    count: usize,
+    len: usize,
+}
+
+impl Pagination {
+    /// `before` and `after` are the fullnames Reddit returned for a page of `len` items.
+    pub fn new(
+        page: &PageRequest,
+        before: Option<String>,
+        after: Option<String>,
+        len: usize,
+    ) -> Pagination {
+        Pagination {
+            before,
+            after,
+            count: page.count,
+            len,
+        }
+    }
+
+    /// 1-based
+    pub fn page(&self) -> usize {
+        self.count / PAGE_SIZE + 1
+    }
+
+    /// Number of items Reddit returned, before anything was hidden
+    pub fn fetched(&self) -> usize {
+        self.len
+    }
+
+    /// Request for the page after this one
+    pub fn next_page(&self) -> Option<PageRequest> {
+        Some(PageRequest {
+            after: Some(self.after.clone()?),
+            before: None,
+            count: self.count + self.len,
+        })
+    }
+
+    /// Request for the page before this one
+    pub fn previous_page(&self) -> Option<PageRequest> {
+        Some(PageRequest {
+            after: None,
+            before: Some(self.before.clone()?),
+            count: self.count.saturating_sub(PAGE_SIZE),
+        })
+    }
+
+    /// Makes this page span `next` too, the page right after it.
+    pub fn extend(&mut self, next: &Pagination) {
+        self.after = next.after.clone();
+        self.len += next.len;
+    }
+
+    /// Local URL of the previous page.
+    /// `uri` is the current one, whose other parameters (sort, filter...) are kept.
+    pub fn before_url(&self, uri: &Uri) -> Option<String> {
+        let before = self.before.as_deref()?;
+        let count = self.count.saturating_sub(PAGE_SIZE);
+
+        Some(page_url(uri, "before", before, count))
+    }
+
+    /// Local URL of the next page.
+    /// `uri` is the current one, whose other parameters (sort, filter...) are kept.
+    pub fn after_url(&self, uri: &Uri) -> Option<String> {
+        let after = self.after.as_deref()?;
+        let count = self.count + self.len;
+
+        Some(page_url(uri, "after", after, count))
+    }
+}
+
+fn page_url(uri: &Uri, key: &str, fullname: &str, count: usize) -> String {
+    let mut url = reqwest::Url::parse("http://localhost/").expect("valid URL");
+    url.set_path(uri.path());
+    url.set_query(uri.query());
+
+    let params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| !matches!(k.as_ref(), "after" | "before" | "count"))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(params)
+        .append_pair(key, fullname)
+        .append_pair("count", &count.to_string());
+
+    match url.query() {
+        Some(q) => format!("{}?{}", url.path(), q),
+        None => url.path().to_string(),
+    }
+}
@@ -0,0 +1,103 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, HeaderValue},
+};
+use cookie::Key;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+
+use crate::{
+    api_types::{CommentSortingMode, SearchSortingMode, SearchTimeOrdering, SortingMode},
+    filters::FilterRules,
+};
+
+const COOKIE_NAME: &str = "prefs";
+
+/// Choices made on `/settings`, kept in a signed cookie.
+/// Missing fields take their default, so cookies from older versions keep working.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    pub sort: SortingMode,
+    pub comment_sort: CommentSortingMode,
+    pub search_sort: SearchSortingMode,
+    /// How far back searches go
+    pub search_time: SearchTimeOrdering,
+    pub hide_nsfw: bool,
+    /// Don't warn before NSFW threads and subreddits
+    pub nsfw_consent: bool,
+    pub image_size: ImageSize,
+    /// How deep comment trees go, `None` is Reddit's default
+    pub comment_depth: Option<u32>,
+    /// The user's own content filters
+    pub filters: FilterRules,
+    /// Fetch more posts when filters leave a page short
+    pub top_up: bool,
+    /// `None` is the server's default theme
+    pub theme: Option<String>,
+    /// Text-only pages for browsers without CSS, see [`crate::legacy`]
+    pub legacy_layout: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ImageSize {
+    #[serde(rename = "small")]
+    Small,
+    #[default]
+    #[serde(rename = "medium")]
+    Medium,
+    #[serde(rename = "large")]
+    Large,
+    #[serde(rename = "full")]
+    Full,
+}
+
+impl ImageSize {
+    /// CSS class for images in posts
+    pub fn class(&self) -> &'static str {
+        match self {
+            ImageSize::Small => "image-small",
+            ImageSize::Medium => "image-medium",
+            ImageSize::Large => "image-large",
+            ImageSize::Full => "image-full",
+        }
+    }
+}
+
+impl Preferences {
+    /// `Set-Cookie` header storing these preferences.
+    pub fn to_cookie(&self, key: &Key) -> Option<HeaderValue> {
+        match serde_json::to_string(self) {
+            Ok(v) => crate::cookies::set_signed(key, COOKIE_NAME, v),
+            Err(e) => {
+                tracing::error!("{}", e);
+                None
+            }
+        }
+    }
+
+    /// `Set-Cookie` header forgetting the preferences.
+    pub fn removal_cookie() -> HeaderValue {
+        crate::cookies::removal(COOKIE_NAME)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Preferences
+where
+    S: Send + Sync,
+    Key: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let key = Key::from_ref(state);
+
+        let prefs = crate::cookies::get_signed(&parts.headers, &key, COOKIE_NAME)
+            .and_then(|v| serde_json::from_str(&v).ok())
+            .unwrap_or_default();
+
+        Ok(prefs)
+    }
+}
@@ -0,0 +1,65 @@
+use axum::{
+    middleware,
+    routing::{get, post},
+    Router,
+};
+
+use crate::{
+    api_v1, comments, health, image_proxy, legacy, metrics, nsfw, schema, search, settings, share,
+    state::AppState, subreddit, subscriptions, themes, upstream, user, wiki,
+};
+
+/// Every page the server has, for HTTP.
+pub fn router(state: AppState) -> Router {
+    let routes = Router::new()
+        .route("/", get(subreddit::front_page))
+        .route("/r/:subreddit", get(subreddit::subreddit))
+        .route("/r/:subreddit/about", get(subreddit::about))
+        .route("/r/:subreddit/subscribe", post(subscriptions::subscribe))
+        .route("/r/:subreddit/unsubscribe", post(subscriptions::unsubscribe))
+        .route("/r/:subreddit/comments/:file", get(comments::comments))
+        .route("/r/:subreddit/comments/:file/*rest", get(comments::permalink))
+        .route("/r/:subreddit/s/:code", get(share::share_link))
+        .route("/r/:subreddit/search", get(search::search_handler))
+        .route("/r/:subreddit/search.rss", get(search::search_rss))
+        .route("/r/:subreddit/search.atom", get(search::search_atom))
+        .route("/r/:subreddit/wiki", get(wiki::wiki_index))
+        .route("/r/:subreddit/wiki/*page", get(wiki::wiki_page))
+        .route("/u/:username", get(user::user))
+        .route("/comments/:id", get(share::short_link))
+        .route("/redd.it/:id", get(share::short_link))
+        .route("/settings", get(settings::settings).post(settings::save_settings))
+        .route("/subscriptions", get(subscriptions::subscriptions))
+        .route("/subscriptions/import", post(subscriptions::import))
+        .route("/nsfw", post(nsfw::allow_nsfw))
+        .route("/i/:id", get(image_proxy::reddit_image_proxy))
+        .route("/t/:id", get(image_proxy::reddit_thumbnail_proxy))
+        .route("/themes/:file", get(themes::stylesheet));
+
+    let routes = if state.client.is_offline() {
+        routes.route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            upstream::not_archived,
+        ))
+    } else {
+        routes
+    };
+
+    let api = Router::new()
+        .route("/r/:subreddit", get(api_v1::subreddit))
+        .route("/r/:subreddit/comments/:id", get(api_v1::comments))
+        .route("/r/:subreddit/wiki", get(api_v1::wiki_index))
+        .route("/r/:subreddit/wiki/*page", get(api_v1::wiki_page))
+        .route("/u/:username", get(api_v1::user));
+
+    routes
+        .clone()
+        .nest(legacy::PREFIX, routes)
+        .nest(api_v1::PREFIX, api)
+        .route("/debug/schema", get(schema::debug_schema))
+        .route("/metrics", get(metrics::metrics))
+        .route("/healthz", get(health::healthz))
+        .route("/readyz", get(health::readyz))
+        .route_layer(middleware::from_fn(metrics::track))
+        .with_state(state)
+}
@@ -0,0 +1,352 @@
+use std::{collections::BTreeMap, fmt, marker::PhantomData, sync::Mutex};
+
+use axum::Json;
+use serde::{
+    de::{
+        self, DeserializeOwned, DeserializeSeed, Error as _, IgnoredAny, IntoDeserializer,
+        MapAccess, SeqAccess, Unexpected, Visitor,
+    },
+    Deserializer, Serialize,
+};
+use serde_json::Value;
+
+/// Kinds Reddit sends that we knowingly skip, like "load more comments" links.
+const DISCARDED_KINDS: [&str; 1] = ["more"];
+
+/// Fields that couldn't be decoded, by kind and field name.
+static FIELD_FAILURES: Mutex<BTreeMap<(&str, &str), FieldFailures>> = Mutex::new(BTreeMap::new());
+
+/// Kinds that aren't decoded at all, and how often they were seen.
+static UNKNOWN_KINDS: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
+
+#[derive(Default)]
+struct FieldFailures {
+    count: u64,
+    last_error: String,
+}
+
+/// Declares a struct whose fields are decoded one by one,
+/// so one Reddit sends in an unexpected shape (a null, another type, nothing)
+/// gets its default value instead of turning the whole page into an error.
+/// Failures are counted for [`debug_schema`].
+macro_rules! lenient {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident as $kind:literal {
+            $(
+                $(#[$field_attr:meta])*
+                $field_vis:vis $field:ident: $ty:ty,
+            )*
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $(
+                $(#[$field_attr])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl<'de> serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct FieldsVisitor;
+
+                impl<'de> serde::de::Visitor<'de> for FieldsVisitor {
+                    type Value = $name;
+
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        f.write_str(concat!("a ", $kind))
+                    }
+
+                    fn visit_map<A>(self, mut map: A) -> Result<$name, A::Error>
+                    where
+                        A: serde::de::MapAccess<'de>,
+                    {
+                        $( let mut $field: Option<$ty> = None; )*
+
+                        while let Some(key) = map.next_key::<String>()? {
+                            match key.as_str() {
+                                $(
+                                    stringify!($field) => {
+                                        $field = Some($crate::schema::Field::decode(
+                                            &mut map,
+                                            $kind,
+                                            stringify!($field),
+                                        )?);
+                                    }
+                                )*
+                                _ => {
+                                    map.next_value::<serde::de::IgnoredAny>()?;
+                                }
+                            }
+                        }
+
+                        Ok($name {
+                            $(
+                                $field: $field.unwrap_or_else(|| {
+                                    $crate::schema::field($kind, stringify!($field), None)
+                                }),
+                            )*
+                        })
+                    }
+                }
+
+                deserializer.deserialize_map(FieldsVisitor)
+            }
+        }
+    };
+}
+
+pub(crate) use lenient;
+
+/// How a field of a [`lenient`] struct is read.
+/// By default the value is read whole first, so it can be replaced by the default if it doesn't fit.
+/// That's only left to fields holding objects, like galleries, whose shape is the likeliest to drift:
+/// strings, numbers and booleans are read in place by [`Scalar`].
+pub trait Field: DeserializeOwned + Default {
+    fn decode<'de, A: MapAccess<'de>>(
+        map: &mut A,
+        kind: &'static str,
+        name: &'static str,
+    ) -> Result<Self, A::Error> {
+        let value: Value = map.next_value()?;
+        Ok(field(kind, name, Some(value)))
+    }
+}
+
+/// Fields read in place, without a [`Value`] in between.
+pub trait ScalarField: DeserializeOwned + Default {}
+
+impl ScalarField for String {}
+impl ScalarField for bool {}
+impl ScalarField for i32 {}
+impl ScalarField for u32 {}
+impl ScalarField for f32 {}
+impl ScalarField for f64 {}
+
+impl<T: ScalarField> Field for T {
+    fn decode<'de, A: MapAccess<'de>>(
+        map: &mut A,
+        kind: &'static str,
+        name: &'static str,
+    ) -> Result<Self, A::Error> {
+        let value = map.next_value_seed(Scalar::new(kind, name))?;
+        Ok(value.unwrap_or_default())
+    }
+}
+
+impl<T: ScalarField> Field for Option<T> {
+    fn decode<'de, A: MapAccess<'de>>(
+        map: &mut A,
+        kind: &'static str,
+        name: &'static str,
+    ) -> Result<Self, A::Error> {
+        map.next_value_seed(OptionalScalar(Scalar::new(kind, name)))
+    }
+}
+
+/// Reads a string, number or boolean in place, whatever Reddit sent instead:
+/// a value of another type is consumed whole so the stream can go on,
+/// counted like other failures, and read as `None`.
+pub struct Scalar<T> {
+    kind: &'static str,
+    name: &'static str,
+    ty: PhantomData<T>,
+}
+
+impl<T: ScalarField> Scalar<T> {
+    pub fn new(kind: &'static str, name: &'static str) -> Self {
+        Scalar {
+            kind,
+            name,
+            ty: PhantomData,
+        }
+    }
+
+    fn read<'de, D>(&self, value: D) -> Option<T>
+    where
+        D: Deserializer<'de, Error = de::value::Error>,
+    {
+        T::deserialize(value)
+            .map_err(|e| failure(self.kind, self.name, &e))
+            .ok()
+    }
+
+    fn mismatch(&self, unexpected: Unexpected) -> Option<T> {
+        let error = de::value::Error::invalid_type(unexpected, &"a string, number or boolean");
+        failure(self.kind, self.name, &error);
+        None
+    }
+}
+
+impl<'de, T: ScalarField> DeserializeSeed<'de> for Scalar<T> {
+    type Value = Option<T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<T>, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, T: ScalarField> Visitor<'de> for Scalar<T> {
+    type Value = Option<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Option<T>, E> {
+        Ok(self.read(v.into_deserializer()))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Option<T>, E> {
+        Ok(self.read(v.into_deserializer()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Option<T>, E> {
+        Ok(self.read(v.into_deserializer()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Option<T>, E> {
+        Ok(self.read(v.into_deserializer()))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Option<T>, E> {
+        Ok(self.read(v.into_deserializer()))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Option<T>, E> {
+        Ok(self.read(().into_deserializer()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Option<T>, A::Error> {
+        while seq.next_element::<IgnoredAny>()?.is_some() {}
+        Ok(self.mismatch(Unexpected::Seq))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Option<T>, A::Error> {
+        while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+        Ok(self.mismatch(Unexpected::Map))
+    }
+}
+
+/// A [`Scalar`] that may be `null`.
+struct OptionalScalar<T>(Scalar<T>);
+
+impl<'de, T: ScalarField> DeserializeSeed<'de> for OptionalScalar<T> {
+    type Value = Option<T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<T>, D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de, T: ScalarField> Visitor<'de> for OptionalScalar<T> {
+    type Value = Option<T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Option<T>, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Option<T>, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<T>, D::Error> {
+        self.0.deserialize(deserializer)
+    }
+}
+
+/// A field of a [`lenient`] struct, or its default when it can't be decoded.
+/// Missing fields are decoded as `null`, which is fine for `Option`s.
+pub fn field<T: DeserializeOwned + Default>(
+    kind: &'static str,
+    name: &'static str,
+    value: Option<Value>,
+) -> T {
+    match serde_json::from_value(value.unwrap_or(Value::Null)) {
+        Ok(v) => v,
+        Err(e) => {
+            failure(kind, name, &e);
+            T::default()
+        }
+    }
+}
+
+/// Counts a field that couldn't be decoded and got its default instead.
+/// Logged the first time, like unknown kinds.
+pub fn failure(kind: &'static str, name: &'static str, error: &dyn std::fmt::Display) {
+    let mut failures = FIELD_FAILURES.lock().unwrap_or_else(|e| e.into_inner());
+    let failure = failures.entry((kind, name)).or_default();
+
+    if failure.count == 0 {
+        tracing::warn!(
+            "Couldn't decode {}.{}, using its default: {}",
+            kind,
+            name,
+            error
+        );
+    }
+    failure.count += 1;
+    failure.last_error = error.to_string();
+}
+
+/// Logged the first time, so a new kind doesn't flood the logs.
+pub fn unknown_kind(kind: &str) {
+    if DISCARDED_KINDS.contains(&kind) {
+        return;
+    }
+
+    let mut kinds = UNKNOWN_KINDS.lock().unwrap_or_else(|e| e.into_inner());
+    let count = kinds.entry(kind.to_string()).or_default();
+
+    if *count == 0 {
+        tracing::warn!("Unknown kind {}, skipping it", kind);
+    }
+    *count += 1;
+}
+
+#[derive(Serialize)]
+pub struct SchemaReport {
+    fields: Vec<FieldReport>,
+    unknown_kinds: BTreeMap<String, u64>,
+}
+
+#[derive(Serialize)]
+struct FieldReport {
+    kind: &'static str,
+    field: &'static str,
+    failures: u64,
+    last_error: String,
+}
+
+/// `/debug/schema`, what Reddit sent since the server started that we couldn't fully read.
+pub async fn debug_schema() -> Json<SchemaReport> {
+    let fields = FIELD_FAILURES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .map(|((kind, field), failure)| FieldReport {
+            kind,
+            field,
+            failures: failure.count,
+            last_error: failure.last_error.clone(),
+        })
+        .collect();
+
+    let unknown_kinds = UNKNOWN_KINDS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+
+    Json(SchemaReport {
+        fields,
+        unknown_kinds,
+    })
+}
@@ -2,14 +2,22 @@ use askama::Template;
 use axum::{
     extract::{Path, Query, State},
     headers::UserAgent,
+    response::{IntoResponse, Response},
     TypedHeader, http::Uri,
 };
-use reqwest::{Client, StatusCode};
+use reqwest::StatusCode;
 use serde::Deserialize;
 
 use crate::{
     api::SubredditQuery,
     api_types::{SearchSortingMode, SearchTimeOrdering},
+    feed::{BaseUrl, Feed, FeedFormat},
+    filters::ContentFilter,
+    legacy::Layout,
+    pagination::PageRequest,
+    preferences::Preferences,
+    themes::CurrentTheme,
+    upstream::Client,
 };
 
 #[derive(Template)]
@@ -17,34 +25,116 @@ use crate::{
 pub struct SearchTemplate {
     subreddit: String,
     data: SubredditQuery,
-    uri: Uri
+    uri: Uri,
+    theme: CurrentTheme,
+}
+
+#[derive(Template)]
+#[template(path = "legacy/search.html")]
+pub struct LegacySearchTemplate {
+    prefix: &'static str,
+    subreddit: String,
+    data: SubredditQuery,
+    uri: Uri,
 }
 
 //t=day
 //sort=comments
 //sort=new
 //sort=relevance
+#[allow(clippy::too_many_arguments)]
 pub async fn search_handler(
     Path(subreddit): Path<String>,
     Query(params): Query<SearchParams>,
+    Query(page): Query<PageRequest>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+    prefs: Preferences,
+    filter: ContentFilter,
+    uri: Uri,
+    theme: CurrentTheme,
+    layout: Layout,
+) -> Result<Response, StatusCode> {
+    let data = search(&client, &subreddit, &params, page, &prefs, &filter, &user_agent).await?;
+
+    if let Layout::Legacy { prefix } = layout {
+        return Ok(crate::legacy::render(LegacySearchTemplate { prefix, subreddit, data, uri }));
+    }
+
+    Ok(SearchTemplate { subreddit, data, uri, theme }.into_response())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn search_rss(
+    Path(subreddit): Path<String>,
+    Query(params): Query<SearchParams>,
+    Query(page): Query<PageRequest>,
     TypedHeader(user_agent): TypedHeader<UserAgent>,
     State(client): State<Client>,
-    uri: Uri
-) -> Result<SearchTemplate, StatusCode> {
-    let data = crate::api::search(
-        &client,
-        &subreddit,
-        &params.q,
-        params.sort,
-        params.t,
-        params.after.as_deref(),
-        params.include_over_18.unwrap_or_default(),
-        params.only_current_subreddit.unwrap_or_default(),
-        user_agent.as_str(),
-    )
-    .await?;
-
-    Ok(SearchTemplate { subreddit, data, uri })
+    prefs: Preferences,
+    filter: ContentFilter,
+    uri: Uri,
+    base: BaseUrl,
+) -> Result<Response, StatusCode> {
+    let data = search(&client, &subreddit, &params, page, &prefs, &filter, &user_agent).await?;
+    Ok(search_feed(&subreddit, &params, &uri, &data).into_response(FeedFormat::Rss, base))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn search_atom(
+    Path(subreddit): Path<String>,
+    Query(params): Query<SearchParams>,
+    Query(page): Query<PageRequest>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+    prefs: Preferences,
+    filter: ContentFilter,
+    uri: Uri,
+    base: BaseUrl,
+) -> Result<Response, StatusCode> {
+    let data = search(&client, &subreddit, &params, page, &prefs, &filter, &user_agent).await?;
+    Ok(search_feed(&subreddit, &params, &uri, &data).into_response(FeedFormat::Atom, base))
+}
+
+async fn search(
+    client: &Client,
+    subreddit: &str,
+    params: &SearchParams,
+    page: PageRequest,
+    prefs: &Preferences,
+    filter: &ContentFilter,
+    user_agent: &UserAgent,
+) -> Result<SubredditQuery, StatusCode> {
+    let fetch = |page: PageRequest| {
+        let user_agent = user_agent.as_str();
+
+        async move {
+            crate::api::search(
+                client,
+                subreddit,
+                &params.q,
+                params.sort,
+                params.t,
+                &page,
+                params.include_over_18.unwrap_or_default(),
+                params.only_current_subreddit.unwrap_or_default(),
+                prefs,
+                user_agent,
+            )
+            .await
+        }
+    };
+
+    let mut data = fetch(page).await?;
+    filter.apply(&mut data, fetch).await;
+
+    Ok(data)
+}
+
+fn search_feed(subreddit: &str, params: &SearchParams, uri: &Uri, data: &SubredditQuery) -> Feed {
+    let title = format!("{} - search in r/{}", params.q, subreddit);
+    let link = format!("/r/{}/search?{}", subreddit, uri.query().unwrap_or_default());
+    Feed::from_posts(title, link, data)
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -54,5 +144,4 @@ pub struct SearchParams {
     t: Option<SearchTimeOrdering>,
     include_over_18: Option<bool>,
     only_current_subreddit: Option<bool>,
-    after: Option<String>,
 }
@@ -0,0 +1,198 @@
+use askama::Template;
+use axum::{
+    extract::State,
+    http::{header, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use cookie::Key;
+use serde::Deserialize;
+
+use crate::{
+    api_types::{CommentSortingMode, SearchSortingMode, SearchTimeOrdering, SortingMode},
+    csrf::SameOrigin,
+    filters::FilterRules,
+    legacy::Layout,
+    preferences::{ImageSize, Preferences},
+    themes::{CurrentTheme, Themes},
+};
+
+/// Rules kept per filter.
+const MAX_RULES: usize = 50;
+
+/// Characters kept per rule, longer ones are cut.
+const MAX_RULE_LENGTH: usize = 100;
+
+/// Browsers drop larger cookies without a word, and every preference with them.
+const MAX_COOKIE_SIZE: usize = 4096;
+
+#[derive(Template)]
+#[template(path = "settings.html")]
+pub struct SettingsTemplate {
+    prefs: Preferences,
+    /// Names of the themes to pick from
+    themes: Vec<String>,
+    theme: CurrentTheme,
+    /// Why the submitted settings weren't saved
+    error: Option<&'static str>,
+}
+
+#[derive(Template)]
+#[template(path = "legacy/settings.html")]
+pub struct LegacySettingsTemplate {
+    prefix: &'static str,
+    prefs: Preferences,
+    themes: Vec<String>,
+    error: Option<&'static str>,
+    uri: Uri,
+}
+
+pub async fn settings(
+    prefs: Preferences,
+    State(themes): State<Themes>,
+    theme: CurrentTheme,
+    layout: Layout,
+    uri: Uri,
+) -> Response {
+    page(prefs, themes, theme, layout, uri, None)
+}
+
+/// The form, filled with `prefs`.
+fn page(
+    prefs: Preferences,
+    themes: Themes,
+    theme: CurrentTheme,
+    layout: Layout,
+    uri: Uri,
+    error: Option<&'static str>,
+) -> Response {
+    if let Layout::Legacy { prefix } = layout {
+        return crate::legacy::render(LegacySettingsTemplate {
+            prefix,
+            prefs,
+            themes: themes.names(),
+            error,
+            uri,
+        });
+    }
+
+    SettingsTemplate {
+        prefs,
+        themes: themes.names(),
+        theme,
+        error,
+    }
+    .into_response()
+}
+
+/// Stores the submitted form in the preferences cookie and goes back to the form.
+/// If the cookie would be too large to keep, the form is shown again with what was submitted.
+pub async fn save_settings(
+    _: SameOrigin,
+    State(key): State<Key>,
+    State(themes): State<Themes>,
+    theme: CurrentTheme,
+    layout: Layout,
+    uri: Uri,
+    Form(form): Form<SettingsForm>,
+) -> Response {
+    let cookie = if form.reset.is_some() {
+        Some(Preferences::removal_cookie())
+    } else {
+        let prefs = Preferences::from(form);
+
+        match prefs.to_cookie(&key) {
+            Some(c) if c.len() > MAX_COOKIE_SIZE => {
+                let error = "Too many filters to save, remove some and try again.";
+                let page = page(prefs, themes, theme, layout, uri, Some(error));
+                return (StatusCode::BAD_REQUEST, page).into_response();
+            }
+            c => c,
+        }
+    };
+
+    let redirect = Redirect::to(&format!("{}/settings", layout.prefix()));
+
+    match cookie {
+        Some(c) => ([(header::SET_COOKIE, c)], redirect).into_response(),
+        None => redirect.into_response(),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SettingsForm {
+    sort: SortingMode,
+    comment_sort: CommentSortingMode,
+    search_sort: SearchSortingMode,
+    search_time: SearchTimeOrdering,
+    /// Checkboxes are only sent when checked
+    hide_nsfw: Option<String>,
+    nsfw_consent: Option<String>,
+    image_size: ImageSize,
+    /// 0 is Reddit's default
+    comment_depth: u32,
+    top_up: Option<String>,
+    /// Empty for the default theme
+    #[serde(default)]
+    theme: String,
+    legacy_layout: Option<String>,
+    /// Filters, one per line
+    #[serde(default)]
+    filter_subreddits: String,
+    #[serde(default)]
+    filter_users: String,
+    #[serde(default)]
+    filter_domains: String,
+    #[serde(default)]
+    filter_keywords: String,
+    #[serde(default)]
+    filter_flairs: String,
+    /// Set by the reset button
+    reset: Option<String>,
+}
+
+impl From<SettingsForm> for Preferences {
+    fn from(form: SettingsForm) -> Self {
+        Preferences {
+            sort: form.sort,
+            comment_sort: form.comment_sort,
+            search_sort: form.search_sort,
+            search_time: form.search_time,
+            hide_nsfw: form.hide_nsfw.is_some(),
+            nsfw_consent: form.nsfw_consent.is_some(),
+            image_size: form.image_size,
+            comment_depth: match form.comment_depth {
+                0 => None,
+                d => Some(d),
+            },
+            filters: FilterRules {
+                subreddits: rules(&form.filter_subreddits, "r/"),
+                users: rules(&form.filter_users, "u/"),
+                domains: rules(&form.filter_domains, ""),
+                keywords: rules(&form.filter_keywords, ""),
+                flairs: rules(&form.filter_flairs, ""),
+            },
+            top_up: form.top_up.is_some(),
+            theme: Some(form.theme).filter(|t| !t.is_empty()),
+            legacy_layout: form.legacy_layout.is_some(),
+        }
+    }
+}
+
+/// One rule per line or comma, without `prefix` (`r/name` is `name`).
+fn rules(text: &str, prefix: &str) -> Vec<String> {
+    let mut rules: Vec<String> = Vec::new();
+
+    for rule in text.split(['\n', ',']) {
+        let rule = rule.trim().trim_start_matches('/');
+        let rule = rule.strip_prefix(prefix).unwrap_or(rule);
+        let rule: String = rule.to_lowercase().chars().take(MAX_RULE_LENGTH).collect();
+
+        if !rule.is_empty() && !rules.contains(&rule) {
+            rules.push(rule);
+        }
+    }
+
+    rules.truncate(MAX_RULES);
+    rules
+}
@@ -0,0 +1,103 @@
+use axum::{
+    extract::{Path, State},
+    headers::UserAgent,
+    http::StatusCode,
+    response::Redirect,
+    TypedHeader,
+};
+
+use crate::{cache::BoundedCache, upstream::Client};
+
+/// A share code leads to the same post for good, so resolved links never go stale:
+/// the limit is only there so made-up codes can't grow the cache without end.
+pub const MAX_CACHED_LINKS: usize = 4096;
+
+/// Resolves share links (`/r/x/s/AbCdEf`) and short links (`redd.it/abc`)
+/// by following Reddit's redirects server-side.
+/// The user's cookies and IP never reach Reddit.
+#[derive(Clone)]
+pub struct ShareLinks {
+    client: Client,
+    base: url_builder::URLBuilder,
+    cache: BoundedCache<String, String>,
+}
+
+impl ShareLinks {
+    /// `base` is the upstream the links are resolved against,
+    /// usually [`crate::utils::get_reddit_domain`].
+    pub fn new(base: url_builder::URLBuilder, client: &Client) -> reqwest::Result<Self> {
+        // redirects are followed by hand, so we stop as soon as we know the post
+        let client = client.without_redirects()?;
+
+        Ok(ShareLinks {
+            client,
+            base,
+            cache: BoundedCache::new("share_links", MAX_CACHED_LINKS),
+        })
+    }
+
+    /// Links resolved so far, out of [`MAX_CACHED_LINKS`].
+    pub async fn cached(&self) -> usize {
+        self.cache.entries().await
+    }
+
+    /// Local path of the post with this id, `/r/:subreddit/comments/:id`.
+    pub async fn post_path(&self, id: &str, user_agent: &str) -> Result<String, StatusCode> {
+        let mut url = self.base.clone();
+        url.add_route("comments");
+        url.add_route(id);
+
+        self.resolve(&url, user_agent).await
+    }
+
+    async fn resolve(
+        &self,
+        url: &url_builder::URLBuilder,
+        user_agent: &str,
+    ) -> Result<String, StatusCode> {
+        let url = url.build();
+
+        if let Some(path) = self.cache.get(&url).await {
+            return Ok(path);
+        }
+
+        let (subreddit, id) = crate::api::resolve_permalink(&self.client, &url, user_agent).await?;
+
+        // the target comes from Reddit, only a valid subreddit and id are kept of it
+        let valid_id = !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric());
+        if !crate::utils::is_subreddit_name(&subreddit) || !valid_id {
+            tracing::warn!("{} leads to /r/{}/comments/{}", url, subreddit, id);
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+        let path = format!("/r/{}/comments/{}", subreddit, id.to_lowercase());
+
+        self.cache.insert(url, path.clone()).await;
+
+        Ok(path)
+    }
+}
+
+pub async fn share_link(
+    Path((subreddit, code)): Path<(String, String)>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(links): State<ShareLinks>,
+) -> Result<Redirect, StatusCode> {
+    let mut url = links.base.clone();
+    url.add_route("r");
+    url.add_route(&subreddit);
+    url.add_route("s");
+    url.add_route(&code);
+
+    let path = links.resolve(&url, user_agent.as_str()).await?;
+    Ok(Redirect::temporary(&path))
+}
+
+/// `redd.it/abc` only redirects to `/comments/abc`, so we start from there.
+pub async fn short_link(
+    Path(id): Path<String>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(links): State<ShareLinks>,
+) -> Result<Redirect, StatusCode> {
+    let path = links.post_path(&id, user_agent.as_str()).await?;
+    Ok(Redirect::temporary(&path))
+}
@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use axum::extract::FromRef;
+use cookie::Key;
+
+use crate::{
+    filters::{Blocklist, FilterRules},
+    nsfw::{NsfwPolicy, NsfwSubreddits},
+    share::ShareLinks,
+    themes::Themes,
+    upstream::Client,
+};
+
+/// Everything the handlers share.
+/// Handlers only extract the part they need, e.g. `State<Client>`.
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub client: Client,
+    pub share_links: ShareLinks,
+    /// Signs the preferences cookie
+    pub cookie_key: Key,
+    pub blocklist: Blocklist,
+    pub nsfw_policy: NsfwPolicy,
+    pub nsfw_subreddits: NsfwSubreddits,
+    pub themes: Themes,
+}
+
+impl AppState {
+    /// Configured from the environment, like the server is.
+    pub fn from_env(client: Client) -> anyhow::Result<AppState> {
+        Ok(AppState {
+            share_links: ShareLinks::new(crate::utils::get_reddit_domain(), &client)?,
+            client,
+            cookie_key: crate::cookies::key()?,
+            blocklist: Blocklist(Arc::new(FilterRules::blocklist()?)),
+            nsfw_policy: NsfwPolicy::from_env(),
+            nsfw_subreddits: NsfwSubreddits::default(),
+            themes: Themes::load()?,
+        })
+    }
+}
@@ -3,14 +3,24 @@ use axum::{
     extract::{Path, Query, State},
     headers::UserAgent,
     http::{StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
     TypedHeader,
 };
-use reqwest::Client;
 use serde::Deserialize;
 
 use crate::{
     api::SubredditQuery,
+    api_result_types::{SubredditRule, T5Data},
     api_types::{SortingMode, TopSortingTime},
+    feed::{BaseUrl, Feed, FeedFormat},
+    filters::ContentFilter,
+    legacy::Layout,
+    nsfw::{NsfwGate, NsfwSubreddits},
+    pagination::PageRequest,
+    preferences::Preferences,
+    subscriptions::Subscriptions,
+    themes::CurrentTheme,
+    upstream::Client,
 };
 
 #[derive(Template)]
@@ -18,32 +28,241 @@ use crate::{
 pub struct SubredditTemplate {
     subreddit: String,
     data: SubredditQuery,
-    uri: Uri
+    uri: Uri,
+    /// Merged subscriptions instead of a subreddit
+    front_page: bool,
+    /// `None` when it can't be subscribed to, like multireddits
+    subscribed: Option<bool>,
+    theme: CurrentTheme,
 }
 
+impl SubredditTemplate {
+    /// A page of a subreddit outside of any request, as the archiver saves them.
+    pub fn new(subreddit: String, data: SubredditQuery, uri: Uri, theme: CurrentTheme) -> Self {
+        SubredditTemplate {
+            subreddit,
+            data,
+            uri,
+            front_page: false,
+            subscribed: None,
+            theme,
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "legacy/subreddit.html")]
+pub struct LegacySubredditTemplate {
+    prefix: &'static str,
+    subreddit: String,
+    data: SubredditQuery,
+    uri: Uri,
+    front_page: bool,
+    subscribed: Option<bool>,
+}
+
+/// Subscribed subreddits merged together, or r/all without subscriptions.
+#[allow(clippy::too_many_arguments)]
+pub async fn front_page(
+    Query(params): Query<SubredditParams>,
+    Query(page): Query<PageRequest>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+    prefs: Preferences,
+    filter: ContentFilter,
+    subs: Subscriptions,
+    uri: Uri,
+    theme: CurrentTheme,
+    layout: Layout,
+) -> Result<Response, StatusCode> {
+    // not permanent, it changes with the subscriptions
+    if subs.is_empty() {
+        let all = format!("{}/r/all", layout.prefix());
+        return Ok(Redirect::to(&all).into_response());
+    }
+
+    let subreddit = subs.token();
+
+    let data = posts(&client, &subreddit, &params, page, &prefs, &filter, &user_agent).await?;
+
+    if let Layout::Legacy { prefix } = layout {
+        return Ok(crate::legacy::render(LegacySubredditTemplate {
+            prefix,
+            subreddit,
+            data,
+            uri,
+            front_page: true,
+            subscribed: None,
+        }));
+    }
+
+    Ok(SubredditTemplate {
+        subreddit,
+        data,
+        uri,
+        front_page: true,
+        subscribed: None,
+        theme,
+    }
+    .into_response())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn subreddit(
     Path(subreddit): Path<String>,
     Query(params): Query<SubredditParams>,
+    Query(page): Query<PageRequest>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+    prefs: Preferences,
+    filter: ContentFilter,
+    gate: NsfwGate,
+    State(nsfw_subreddits): State<NsfwSubreddits>,
+    subs: Subscriptions,
+    uri: Uri,
+    base: BaseUrl,
+    theme: CurrentTheme,
+    layout: Layout,
+) -> Result<Response, StatusCode> {
+    let (subreddit, format) = FeedFormat::split(&subreddit);
+    let subreddit = subreddit.to_string();
+
+    // multireddits, r/all... have their NSFW posts marked one by one
+    let nsfw = Subscriptions::can_subscribe(&subreddit)
+        && !gate.is_open()
+        && nsfw_subreddits
+            .is_nsfw(&client, &subreddit, user_agent.as_str())
+            .await;
+
+    let warning = match format {
+        Some(_) => gate.check_disabled(nsfw),
+        None => gate.check(nsfw, &uri),
+    };
+
+    if let Some(warning) = warning {
+        return Ok(warning);
+    }
+
+    let data = posts(&client, &subreddit, &params, page, &prefs, &filter, &user_agent).await?;
+
+    if let Some(format) = format {
+        let feed = Feed::from_posts(format!("r/{}", subreddit), format!("/r/{}", subreddit), &data);
+        return Ok(feed.into_response(format, base));
+    }
+
+    let subscribed = Subscriptions::can_subscribe(&subreddit).then(|| subs.contains(&subreddit));
+
+    if let Layout::Legacy { prefix } = layout {
+        return Ok(crate::legacy::render(LegacySubredditTemplate {
+            prefix,
+            subreddit,
+            data,
+            uri,
+            front_page: false,
+            subscribed,
+        }));
+    }
+
+    Ok(SubredditTemplate {
+        subreddit,
+        data,
+        uri,
+        front_page: false,
+        subscribed,
+        theme,
+    }
+    .into_response())
+}
+
+/// Fetches a page of posts, without what the user filters out.
+async fn posts(
+    client: &Client,
+    subreddit: &str,
+    params: &SubredditParams,
+    page: PageRequest,
+    prefs: &Preferences,
+    filter: &ContentFilter,
+    user_agent: &UserAgent,
+) -> Result<SubredditQuery, StatusCode> {
+    let fetch = |page: PageRequest| async move {
+        crate::api::subreddit(
+            client,
+            subreddit,
+            params.sort,
+            params.t,
+            &page,
+            prefs,
+            user_agent.as_str(),
+        )
+        .await
+    };
+
+    let mut data = fetch(page).await?;
+    filter.apply(&mut data, fetch).await;
+
+    Ok(data)
+}
+
+#[derive(Template)]
+#[template(path = "subreddit_about.html")]
+pub struct SubredditAboutTemplate {
+    subreddit: String,
+    data: T5Data,
+    rules: Vec<SubredditRule>,
+    theme: CurrentTheme,
+}
+
+#[derive(Template)]
+#[template(path = "legacy/subreddit_about.html")]
+pub struct LegacySubredditAboutTemplate {
+    prefix: &'static str,
+    subreddit: String,
+    data: T5Data,
+    rules: Vec<SubredditRule>,
+    uri: Uri,
+}
+
+pub async fn about(
+    Path(subreddit): Path<String>,
     TypedHeader(user_agent): TypedHeader<UserAgent>,
     State(client): State<Client>,
-    uri: Uri
-) -> Result<SubredditTemplate, StatusCode> {
-    let data = crate::api::subreddit(
-        &client,
-        &subreddit,
-        params.sort,
-        params.t,
-        params.after.as_deref(),
-        user_agent.as_str(),
-    )
-    .await?;
+    gate: NsfwGate,
+    uri: Uri,
+    theme: CurrentTheme,
+    layout: Layout,
+) -> Result<Response, StatusCode> {
+    let (mut data, rules) = tokio::try_join!(
+        crate::api::subreddit_about(&client, &subreddit, user_agent.as_str()),
+        crate::api::subreddit_rules(&client, &subreddit, user_agent.as_str()),
+    )?;
+
+    if let Some(warning) = gate.check(data.is_nsfw(), &uri) {
+        return Ok(warning);
+    }
+
+    data.description = crate::utils::rewrite_reddit_links(&data.description);
+
+    if let Layout::Legacy { prefix } = layout {
+        return Ok(crate::legacy::render(LegacySubredditAboutTemplate {
+            prefix,
+            subreddit,
+            data,
+            rules,
+            uri,
+        }));
+    }
 
-    Ok(SubredditTemplate { subreddit, data, uri })
+    Ok(SubredditAboutTemplate {
+        subreddit,
+        data,
+        rules,
+        theme,
+    }
+    .into_response())
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct SubredditParams {
     sort: Option<SortingMode>,
     t: Option<TopSortingTime>,
-    after: Option<String>,
 }
@@ -0,0 +1,268 @@
+use askama::Template;
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Multipart, Path, State},
+    http::{header, request::Parts, HeaderValue, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use cookie::Key;
+use serde::Deserialize;
+use std::convert::Infallible;
+
+use crate::{csrf::SameOrigin, legacy::Layout, themes::CurrentTheme};
+
+const COOKIE_NAME: &str = "subs";
+
+/// Reddit won't merge more than this, and the cookie has to stay under 4KB.
+const MAX_SUBSCRIPTIONS: usize = 100;
+
+/// Subreddits followed without an account, kept in a signed cookie.
+/// Stored as a `a+b+c` token, which also is what users export and import.
+#[derive(Debug, Clone, Default)]
+pub struct Subscriptions(Vec<String>);
+
+impl Subscriptions {
+    /// Reads subreddit names from a token, a pasted list of names or links, or an OPML file.
+    pub fn import(text: &str) -> Subscriptions {
+        let mut subs = Subscriptions::default();
+
+        if text.trim_start().starts_with('<') {
+            subs.import_opml(text);
+            return subs;
+        }
+
+        for token in text.split(|c: char| c.is_whitespace() || c == ',' || c == '+') {
+            if let Some(name) = subreddit_name(token) {
+                subs.add(name);
+            }
+        }
+
+        subs
+    }
+
+    /// Feed readers export OPML, where subreddits are feed URLs.
+    fn import_opml(&mut self, text: &str) {
+        let doc = match roxmltree::Document::parse(text) {
+            Ok(d) => d,
+            Err(e) => {
+                tracing::warn!("Invalid OPML: {}", e);
+                return;
+            }
+        };
+
+        for outline in doc.descendants().filter(|n| n.has_tag_name("outline")) {
+            let name = ["xmlUrl", "htmlUrl"]
+                .iter()
+                .filter_map(|a| outline.attribute(*a))
+                .find_map(subreddit_name);
+
+            if let Some(name) = name {
+                self.add(name);
+            }
+        }
+    }
+
+    pub fn add(&mut self, subreddit: &str) {
+        if self.0.len() >= MAX_SUBSCRIPTIONS || self.contains(subreddit) {
+            return;
+        }
+
+        self.0.push(subreddit.to_string());
+        self.0.sort_by_key(|s| s.to_lowercase());
+    }
+
+    pub fn remove(&mut self, subreddit: &str) {
+        self.0.retain(|s| !s.eq_ignore_ascii_case(subreddit));
+    }
+
+    pub fn merge(&mut self, other: Subscriptions) {
+        for s in other.0 {
+            self.add(&s);
+        }
+    }
+
+    pub fn contains(&self, subreddit: &str) -> bool {
+        self.0.iter().any(|s| s.eq_ignore_ascii_case(subreddit))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.0
+    }
+
+    /// Multireddit name, `a+b+c`
+    pub fn token(&self) -> String {
+        self.0.join("+")
+    }
+
+    /// Whether `subreddit` is a single subreddit one can subscribe to.
+    pub fn can_subscribe(subreddit: &str) -> bool {
+        is_valid_name(subreddit)
+    }
+
+    fn to_cookie(&self, key: &Key) -> Option<HeaderValue> {
+        if self.is_empty() {
+            Some(crate::cookies::removal(COOKIE_NAME))
+        } else {
+            crate::cookies::set_signed(key, COOKIE_NAME, self.token())
+        }
+    }
+
+    /// Stores the subscriptions and goes to `to`.
+    fn save(&self, key: &Key, to: &str) -> Response {
+        let redirect = Redirect::to(to);
+
+        match self.to_cookie(key) {
+            Some(c) => ([(header::SET_COOKIE, c)], redirect).into_response(),
+            None => redirect.into_response(),
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Subscriptions
+where
+    S: Send + Sync,
+    Key: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let key = Key::from_ref(state);
+
+        let subs = crate::cookies::get_signed(&parts.headers, &key, COOKIE_NAME)
+            .map(|v| Subscriptions::import(&v))
+            .unwrap_or_default();
+
+        Ok(subs)
+    }
+}
+
+/// Subreddit name out of `name`, `r/name` or a link to the subreddit.
+fn subreddit_name(token: &str) -> Option<&str> {
+    let name = if token.contains('/') {
+        let mut segments = token.split('/');
+        segments.find(|s| *s == "r")?;
+        let segment = segments.next()?;
+
+        // feed links, `/r/name.rss`
+        segment
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .next()?
+    } else {
+        token
+    };
+
+    is_valid_name(name).then_some(name)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    // these aren't subreddits and break multireddits
+    let reserved = ["all", "popular", "random", "friends", "mod"];
+
+    (2..=21).contains(&name.len())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !reserved.iter().any(|r| r.eq_ignore_ascii_case(name))
+}
+
+#[derive(Template)]
+#[template(path = "subscriptions.html")]
+pub struct SubscriptionsTemplate {
+    subs: Subscriptions,
+    theme: CurrentTheme,
+}
+
+#[derive(Template)]
+#[template(path = "legacy/subscriptions.html")]
+pub struct LegacySubscriptionsTemplate {
+    prefix: &'static str,
+    subs: Subscriptions,
+    uri: Uri,
+}
+
+pub async fn subscriptions(
+    subs: Subscriptions,
+    theme: CurrentTheme,
+    layout: Layout,
+    uri: Uri,
+) -> Response {
+    if let Layout::Legacy { prefix } = layout {
+        return crate::legacy::render(LegacySubscriptionsTemplate { prefix, subs, uri });
+    }
+
+    SubscriptionsTemplate { subs, theme }.into_response()
+}
+
+pub async fn subscribe(
+    _: SameOrigin,
+    Path(subreddit): Path<String>,
+    State(key): State<Key>,
+    mut subs: Subscriptions,
+    Form(form): Form<SubscribeForm>,
+) -> Result<Response, StatusCode> {
+    if !Subscriptions::can_subscribe(&subreddit) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    subs.add(&subreddit);
+    Ok(subs.save(&key, &form.back_to(&subreddit)))
+}
+
+pub async fn unsubscribe(
+    _: SameOrigin,
+    Path(subreddit): Path<String>,
+    State(key): State<Key>,
+    mut subs: Subscriptions,
+    Form(form): Form<SubscribeForm>,
+) -> Result<Response, StatusCode> {
+    if !Subscriptions::can_subscribe(&subreddit) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    subs.remove(&subreddit);
+    Ok(subs.save(&key, &form.back_to(&subreddit)))
+}
+
+/// Merges a pasted list and/or an uploaded OPML file into the subscriptions.
+pub async fn import(
+    _: SameOrigin,
+    State(key): State<Key>,
+    mut subs: Subscriptions,
+    layout: Layout,
+    mut multipart: Multipart,
+) -> Result<Response, StatusCode> {
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+    {
+        if !matches!(field.name(), Some("list" | "opml")) {
+            continue;
+        }
+
+        let text = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+        subs.merge(Subscriptions::import(&text));
+    }
+
+    Ok(subs.save(&key, &format!("{}/subscriptions", layout.prefix())))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SubscribeForm {
+    /// Local page to go back to, the subreddit if missing
+    back: Option<String>,
+}
+
+impl SubscribeForm {
+    fn back_to(&self, subreddit: &str) -> String {
+        match &self.back {
+            // we aren't an open redirect
+            Some(b) if crate::utils::is_local_path(b) => b.clone(),
+            _ => format!("/r/{}", subreddit),
+        }
+    }
+}
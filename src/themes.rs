@@ -0,0 +1,177 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    convert::Infallible,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, Path, State},
+    http::{header, request::Parts, StatusCode},
+    response::{IntoResponse, Response},
+};
+use cookie::Key;
+
+use crate::preferences::Preferences;
+
+/// Environment variable pointing at a directory of `<name>.css` files,
+/// added to the built-in themes or replacing them.
+const THEMES_VAR: &str = "OLDER_REDDIT_THEMES";
+
+pub const DEFAULT_THEME: &str = "dark";
+
+/// Layout shared by every theme, which only set colors on top of it.
+const BASE: &str = "base";
+
+const BUILT_IN: [(&str, &str); 3] = [
+    ("dark", include_str!("../themes/dark.css")),
+    ("light", include_str!("../themes/light.css")),
+    ("high-contrast", include_str!("../themes/high-contrast.css")),
+];
+
+/// Stylesheet URLs carry a hash of their content,
+/// so they can be cached forever and still change when the file does.
+const CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+struct Stylesheet {
+    css: String,
+    version: String,
+}
+
+impl Stylesheet {
+    fn new(css: String) -> Stylesheet {
+        let mut hasher = DefaultHasher::new();
+        css.hash(&mut hasher);
+
+        Stylesheet {
+            css,
+            version: format!("{:016x}", hasher.finish()),
+        }
+    }
+}
+
+/// Every theme users can pick from.
+#[derive(Clone)]
+pub struct Themes {
+    base: Arc<Stylesheet>,
+    themes: Arc<BTreeMap<String, Stylesheet>>,
+}
+
+impl Themes {
+    /// Built-in themes, plus the ones in the configured directory.
+    pub fn load() -> anyhow::Result<Themes> {
+        let mut themes: BTreeMap<String, Stylesheet> = BUILT_IN
+            .iter()
+            .map(|(name, css)| (name.to_string(), Stylesheet::new(css.to_string())))
+            .collect();
+
+        if let Ok(dir) = std::env::var(THEMES_VAR) {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+
+                if path.extension().is_some_and(|e| e == "css") {
+                    let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                        continue;
+                    };
+
+                    if !is_valid_name(name) {
+                        tracing::warn!("Skipping theme {}, bad name", path.display());
+                        continue;
+                    }
+
+                    let css = std::fs::read_to_string(&path)?;
+                    themes.insert(name.to_string(), Stylesheet::new(css));
+                    tracing::info!("Loaded theme {}", name);
+                }
+            }
+        }
+
+        Ok(Themes {
+            base: Arc::new(Stylesheet::new(
+                include_str!("../themes/base.css").to_string(),
+            )),
+            themes: Arc::new(themes),
+        })
+    }
+
+    /// CSS of the theme called `name`, or of the shared layout for `base`.
+    pub fn css(&self, name: &str) -> Option<&str> {
+        if name == BASE {
+            return Some(&self.base.css);
+        }
+        self.themes.get(name).map(|t| t.css.as_str())
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.themes.keys().cloned().collect()
+    }
+
+    /// Stylesheets for `name`, the default theme if it doesn't exist.
+    pub fn current(&self, name: Option<&str>) -> CurrentTheme {
+        let name = name
+            .filter(|n| self.themes.contains_key(*n))
+            .unwrap_or(DEFAULT_THEME);
+
+        let version = self
+            .themes
+            .get(name)
+            .map(|t| t.version.as_str())
+            .unwrap_or_default();
+
+        CurrentTheme {
+            base_url: format!("/themes/{}.css?v={}", BASE, self.base.version),
+            url: format!("/themes/{}.css?v={}", name, version),
+        }
+    }
+}
+
+/// `base` is taken by the layout.
+fn is_valid_name(name: &str) -> bool {
+    name != BASE
+        && !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Theme of the user making the request, for templates to link to.
+#[derive(Debug, Clone)]
+pub struct CurrentTheme {
+    pub base_url: String,
+    pub url: String,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentTheme
+where
+    S: Send + Sync,
+    Key: FromRef<S>,
+    Themes: FromRef<S>,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let prefs = Preferences::from_request_parts(parts, state).await?;
+        Ok(Themes::from_ref(state).current(prefs.theme.as_deref()))
+    }
+}
+
+/// `/themes/<name>.css`
+pub async fn stylesheet(
+    Path(file): Path<String>,
+    State(themes): State<Themes>,
+) -> Result<Response, StatusCode> {
+    let name = file.strip_suffix(".css").ok_or(StatusCode::NOT_FOUND)?;
+
+    let css = themes.css(name).ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/css; charset=utf-8"),
+            (header::CACHE_CONTROL, CACHE_CONTROL),
+        ],
+        css.to_string(),
+    )
+        .into_response())
+}
@@ -0,0 +1,489 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use anyhow::{anyhow, bail, Context};
+use askama::Template;
+use axum::{
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response as HttpResponse},
+};
+use bytes::Bytes;
+use reqwest::{
+    header::{HeaderName, CONTENT_TYPE, LOCATION, USER_AGENT},
+    redirect::Policy,
+    StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::themes::CurrentTheme;
+
+/// Where saved API responses go inside a snapshot store, next to the archived media.
+const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Redirects are saved as their target, in a file next to where the body would be.
+const LOCATION_SUFFIX: &str = ".location";
+
+/// Everything the server asks Reddit goes through here,
+/// so it can be answered from a snapshot store instead (`--offline`),
+/// or saved into one (`--record`).
+#[derive(Clone, Default)]
+pub struct Client {
+    http: reqwest::Client,
+    snapshots: Option<Snapshots>,
+    /// Shared by every clone, see [`Client::last_answer`]
+    last_answer: Arc<Mutex<Option<Answer>>>,
+}
+
+/// How Reddit last answered an API call.
+#[derive(Clone, Copy)]
+pub struct Answer {
+    pub at: Instant,
+    /// `None` if Reddit couldn't be reached
+    pub status: Option<StatusCode>,
+    /// As of the last answer that said anything about it
+    pub ratelimit: Option<RateLimit>,
+}
+
+#[derive(Clone, Copy, Serialize)]
+pub struct RateLimit {
+    /// Requests left until the reset
+    pub remaining: f64,
+    pub reset_seconds: f64,
+}
+
+#[derive(Clone)]
+struct Snapshots {
+    dir: Arc<PathBuf>,
+    mode: Mode,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Reddit is never asked, missing responses are 404s
+    Offline,
+    /// Reddit is asked, and successful responses are saved
+    Record,
+}
+
+/// Reddit's rate limit, sent with every answer.
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// A response read in full, from Reddit or from the store.
+pub struct Response {
+    /// What was asked, see [`endpoint`]
+    endpoint: &'static str,
+    status: StatusCode,
+    location: Option<String>,
+    content_type: Option<String>,
+    body: Bytes,
+}
+
+impl Response {
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// Where a redirect leads.
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// Only known for live responses.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Failures are counted in the metrics, by endpoint.
+    pub fn json<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        let json = serde_json::from_slice(&self.body);
+        if json.is_err() {
+            crate::metrics::decode_failure(self.endpoint);
+        }
+        json
+    }
+
+    pub fn bytes(self) -> Bytes {
+        self.body
+    }
+
+    fn not_archived() -> Response {
+        Response {
+            endpoint: "",
+            status: StatusCode::NOT_FOUND,
+            location: None,
+            content_type: None,
+            body: Bytes::new(),
+        }
+    }
+}
+
+impl Client {
+    /// Straight to Reddit.
+    pub fn new() -> Client {
+        Client::default()
+    }
+
+    /// Answers everything from the store in `dir`, which is usually an archive.
+    pub fn offline(dir: impl Into<PathBuf>) -> Client {
+        Client {
+            snapshots: Some(Snapshots {
+                dir: Arc::new(dir.into()),
+                mode: Mode::Offline,
+            }),
+            ..Client::default()
+        }
+    }
+
+    /// Asks Reddit, and saves what it answered into the store in `dir`.
+    pub fn record(dir: impl Into<PathBuf>) -> Client {
+        Client {
+            snapshots: Some(Snapshots {
+                dir: Arc::new(dir.into()),
+                mode: Mode::Record,
+            }),
+            ..Client::default()
+        }
+    }
+
+    /// From the server's arguments, `--offline DIR` or `--record DIR`.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Client> {
+        let client = match args.next().as_deref() {
+            None => Client::new(),
+            Some("--offline") => {
+                let dir = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--offline needs a directory"))?;
+                if !Path::new(&dir).is_dir() {
+                    bail!("{} isn't a directory", dir);
+                }
+                tracing::info!("Serving offline from {}", dir);
+                Client::offline(dir)
+            }
+            Some("--record") => {
+                let dir = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--record needs a directory"))?;
+                tracing::info!("Recording Reddit's responses into {}", dir);
+                Client::record(dir)
+            }
+            Some(arg) => bail!(
+                "Unknown argument {}, expected --offline DIR or --record DIR",
+                arg
+            ),
+        };
+
+        if let Some(arg) = args.next() {
+            bail!("Unexpected argument {}", arg);
+        }
+
+        Ok(client)
+    }
+
+    /// The same store, for callers that follow redirects themselves,
+    /// see [`crate::api::resolve_permalink`].
+    pub fn without_redirects(&self) -> reqwest::Result<Client> {
+        Ok(Client {
+            http: reqwest::Client::builder()
+                .redirect(Policy::none())
+                .build()?,
+            snapshots: self.snapshots.clone(),
+            last_answer: self.last_answer.clone(),
+        })
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.snapshots
+            .as_ref()
+            .is_some_and(|s| s.mode == Mode::Offline)
+    }
+
+    /// How Reddit last answered an API call made by this client or its clones,
+    /// `None` until one was made. Media isn't counted, it isn't served by the API.
+    pub fn last_answer(&self) -> Option<Answer> {
+        *self.last_answer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn answered(&self, url: &str, status: Option<StatusCode>, ratelimit: Option<RateLimit>) {
+        if crate::image_proxy::media_file(url).is_some() {
+            return;
+        }
+
+        let mut last = self.last_answer.lock().unwrap_or_else(|e| e.into_inner());
+        let ratelimit = ratelimit.or_else(|| last.and_then(|a| a.ratelimit));
+
+        *last = Some(Answer {
+            at: Instant::now(),
+            status,
+            ratelimit,
+        });
+    }
+
+    pub async fn get(&self, url: &str, user_agent: &str) -> anyhow::Result<Response> {
+        let endpoint = endpoint(url);
+
+        let snapshots = match &self.snapshots {
+            Some(s) => s,
+            None => return self.fetch(url, endpoint, user_agent).await,
+        };
+
+        let file = snapshots.dir.join(snapshot_file(url)?);
+
+        match snapshots.mode {
+            Mode::Offline => {
+                let mut response = read(&file).await?;
+                response.endpoint = endpoint;
+                Ok(response)
+            }
+            Mode::Record => {
+                let response = self.fetch(url, endpoint, user_agent).await?;
+
+                // a page that can't be saved is still worth showing
+                if let Err(e) = write(&file, &response).await {
+                    tracing::warn!("Couldn't record {}: {:#}", url, e);
+                }
+                Ok(response)
+            }
+        }
+    }
+
+    /// Asks Reddit, counting the request and its answer in the metrics.
+    async fn fetch(
+        &self,
+        url: &str,
+        endpoint: &'static str,
+        user_agent: &str,
+    ) -> anyhow::Result<Response> {
+        let start = Instant::now();
+        let response = self
+            .http
+            .get(url)
+            .header(USER_AGENT, user_agent)
+            .send()
+            .await;
+
+        let response = match response {
+            Ok(r) => r,
+            Err(e) => {
+                crate::metrics::upstream_request(endpoint, None, start);
+                self.answered(url, None, None);
+                return Err(e.into());
+            }
+        };
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|h| h.to_str().ok())
+                .map(str::to_string)
+        };
+
+        let ratelimit = match (
+            header(RATELIMIT_REMAINING).and_then(|h| h.parse().ok()),
+            header(RATELIMIT_RESET).and_then(|h| h.parse().ok()),
+        ) {
+            (Some(remaining), Some(reset_seconds)) => {
+                crate::metrics::ratelimit(remaining, reset_seconds);
+                Some(RateLimit {
+                    remaining,
+                    reset_seconds,
+                })
+            }
+            _ => None,
+        };
+
+        let status = response.status();
+        self.answered(url, Some(status), ratelimit);
+        let location = header(LOCATION);
+        let content_type = header(CONTENT_TYPE);
+        let body = response.bytes().await;
+        crate::metrics::upstream_request(endpoint, Some(status), start);
+
+        Ok(Response {
+            endpoint,
+            status,
+            location,
+            content_type,
+            body: body?,
+        })
+    }
+}
+
+async fn read(file: &Path) -> anyhow::Result<Response> {
+    match tokio::fs::read(file).await {
+        Ok(body) => {
+            return Ok(Response {
+                endpoint: "",
+                status: StatusCode::OK,
+                location: None,
+                content_type: None,
+                body: body.into(),
+            })
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("Couldn't read {}", file.display())),
+    }
+
+    match tokio::fs::read_to_string(location_file(file)).await {
+        Ok(location) => Ok(Response {
+            endpoint: "",
+            status: StatusCode::MOVED_PERMANENTLY,
+            location: Some(location),
+            content_type: None,
+            body: Bytes::new(),
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            tracing::info!("Not archived: {}", file.display());
+            Ok(Response::not_archived())
+        }
+        Err(e) => Err(e).with_context(|| format!("Couldn't read {}", file.display())),
+    }
+}
+
+/// Only what's worth serving is kept, errors are asked again next time.
+async fn write(file: &Path, response: &Response) -> anyhow::Result<()> {
+    if let Some(dir) = file.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+
+    if response.status.is_success() {
+        tokio::fs::write(file, &response.body).await?;
+    } else if let (true, Some(location)) = (response.status.is_redirection(), &response.location) {
+        tokio::fs::write(location_file(file), location).await?;
+    }
+
+    Ok(())
+}
+
+fn location_file(file: &Path) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(LOCATION_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Where the response to `url` is kept, relative to the store.
+///
+/// Images are kept under `i/` and `t/` like in archives, API responses under [`SNAPSHOT_DIR`],
+/// named after the URL without its host, with the subreddit lowercased and the query sorted.
+/// The rest keeps its case, share codes and ids that differ only by case are different things.
+fn snapshot_file(url: &str) -> anyhow::Result<PathBuf> {
+    if let Some(file) = crate::image_proxy::media_file(url) {
+        return Ok(PathBuf::from(file));
+    }
+
+    let url = Url::parse(url)?;
+
+    let mut segments: Vec<String> = Vec::new();
+    for segment in url.path_segments().into_iter().flatten() {
+        if segment.is_empty() {
+            continue;
+        }
+
+        let segment = match segments.last() {
+            Some(r) if r == "r" => segment.to_lowercase(),
+            _ => segment.to_string(),
+        };
+        segments.push(escape(&segment));
+    }
+
+    if segments.is_empty() {
+        segments.push("index".to_string());
+    }
+
+    let mut query: Vec<_> = url.query_pairs().collect();
+    if !query.is_empty() {
+        query.sort();
+        let query = serde_urlencoded::to_string(query)?;
+
+        if let Some(last) = segments.last_mut() {
+            last.push_str(&escape(&format!("?{}", query)));
+        }
+    }
+
+    let mut file = PathBuf::from(SNAPSHOT_DIR);
+    file.extend(segments);
+    Ok(file)
+}
+
+/// What `url` asks Reddit for, as a label for the metrics: the API call, or the kind of media.
+fn endpoint(url: &str) -> &'static str {
+    if let Some(file) = crate::image_proxy::media_file(url) {
+        return if file.starts_with("t/") {
+            "thumbnail"
+        } else {
+            "image"
+        };
+    }
+
+    let url = match Url::parse(url) {
+        Ok(u) => u,
+        Err(_) => return "other",
+    };
+    let segments: Vec<_> = url
+        .path_segments()
+        .into_iter()
+        .flatten()
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    match segments.as_slice() {
+        ["r", _, "comments", ..] => "comments",
+        ["r", _, "s", _] | ["comments", _] => "share_link",
+        ["r", _, "about.json"] => "subreddit_about",
+        ["r", _, "about", "rules.json"] => "subreddit_rules",
+        ["r", _, "search.json"] => "search",
+        ["r", _, "wiki", "pages.json"] => "wiki_pages",
+        ["r", _, "wiki", "revisions.json" | "revisions", ..] => "wiki_revisions",
+        ["r", _, "wiki", ..] => "wiki",
+        ["r", ..] => "subreddit",
+        ["user", _, "about.json"] => "user_about",
+        ["user", _, "trophies.json"] => "user_trophies",
+        ["user", ..] => "user",
+        _ => "other",
+    }
+}
+
+/// Percent-encodes everything that could be unsafe in a file name,
+/// including leading dots so nothing escapes the store.
+fn escape(segment: &str) -> String {
+    let mut escaped = String::with_capacity(segment.len());
+
+    for (i, b) in segment.bytes().enumerate() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'-' => escaped.push(b as char),
+            b'.' if i > 0 => escaped.push('.'),
+            b => escaped.push_str(&format!("%{:02X}", b)),
+        }
+    }
+
+    escaped
+}
+
+#[derive(Template)]
+#[template(path = "not_archived.html")]
+struct NotArchivedTemplate {
+    path: String,
+    theme: CurrentTheme,
+}
+
+/// Middleware for `--offline`, telling pages that weren't archived apart from other errors.
+/// Every 404 is one, as Reddit's own are never saved.
+pub async fn not_archived<B>(
+    theme: CurrentTheme,
+    request: Request<B>,
+    next: Next<B>,
+) -> HttpResponse {
+    let path = request.uri().path().to_string();
+    let response = next.run(request).await;
+
+    if response.status() != StatusCode::NOT_FOUND {
+        return response;
+    }
+
+    (StatusCode::NOT_FOUND, NotArchivedTemplate { path, theme }).into_response()
+}
@@ -3,41 +3,135 @@ use axum::{
     extract::{Path, Query, State},
     headers::UserAgent,
     http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
     TypedHeader,
 };
-use reqwest::Client;
 use serde::Deserialize;
 use crate::api_result_types::RedditData;
 
-use crate::{api_types::{UserSortingMode, UserFilterMode, SearchTimeOrdering}, api_result_types::ListingData};
+use crate::{api::UserQuery, api_types::{UserSortingMode, UserFilterMode, SearchTimeOrdering}, api_result_types::{T2Data, T6Data}, feed::{BaseUrl, Feed, FeedFormat}, filters::ContentFilter, legacy::Layout, pagination::{PageRequest, Pagination}, themes::CurrentTheme, upstream::Client};
 
 #[derive(Template)]
 #[template(path = "user.html")]
 pub struct UserTemplate {
     username: String,
-    data: ListingData,
-    uri: Uri
+    /// `None` if the account is deleted or shadowbanned, or Reddit failed to send it
+    about: Option<T2Data>,
+    /// Reddit says the account doesn't exist
+    deleted: bool,
+    trophies: Vec<T6Data>,
+    data: UserQuery,
+    uri: Uri,
+    theme: CurrentTheme,
 }
 
+#[derive(Template)]
+#[template(path = "legacy/user.html")]
+pub struct LegacyUserTemplate {
+    prefix: &'static str,
+    username: String,
+    about: Option<T2Data>,
+    deleted: bool,
+    trophies: Vec<T6Data>,
+    data: UserQuery,
+    uri: Uri,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn user(
     Path(username): Path<String>,
     Query(params): Query<UserParams>,
+    Query(page): Query<PageRequest>,
     TypedHeader(user_agent): TypedHeader<UserAgent>,
     State(client): State<Client>,
-    uri: Uri
-) -> Result<UserTemplate, StatusCode> {
-    let data = crate::api::user(
-        &client,
-        &username,
-        params.sort,
-        params.t,
-        params.filter,
-        params.after.as_deref(),
-        user_agent.as_str(),
-    )
-    .await?;
-
-    Ok(UserTemplate { username, data, uri })
+    filter: ContentFilter,
+    uri: Uri,
+    base: BaseUrl,
+    theme: CurrentTheme,
+    layout: Layout,
+) -> Result<Response, StatusCode> {
+    let (username, format) = FeedFormat::split(&username);
+    let username = username.to_string();
+
+    let fetch = |page: PageRequest| {
+        let (client, username, params) = (&client, &username, &params);
+        let user_agent = user_agent.as_str();
+
+        async move {
+            crate::api::user(
+                client,
+                username,
+                params.sort,
+                params.t,
+                params.filter,
+                &page,
+                user_agent,
+            )
+            .await
+        }
+    };
+
+    if let Some(format) = format {
+        let mut data = fetch(page).await?;
+        filter.apply(&mut data, fetch).await;
+
+        let feed = Feed::from_user(format!("u/{}", username), format!("/u/{}", username), &data);
+        return Ok(feed.into_response(format, base));
+    }
+
+    let (about, trophies, data) = tokio::join!(
+        crate::api::user_about(&client, &username, user_agent.as_str()),
+        crate::api::user_trophies(&client, &username, user_agent.as_str()),
+        fetch(page),
+    );
+
+    // deleted and shadowbanned accounts don't exist as far as the API is concerned,
+    // and the page is still worth showing without the header when Reddit fails to send it
+    let (about, deleted) = match about {
+        Ok(a) => (Some(a), false),
+        Err(StatusCode::NOT_FOUND) => (None, true),
+        Err(e) => {
+            tracing::warn!("Couldn't fetch u/{}'s about: {}", username, e);
+            (None, false)
+        }
+    };
+
+    let suspended = about.as_ref().map_or(deleted, |a| a.is_suspended);
+
+    // their listings are gone too, so we show the header alone
+    let mut data = match data {
+        Ok(d) => d,
+        Err(_) if suspended => UserQuery {
+            children: Vec::new(),
+            pagination: Pagination::default(),
+            hidden: 0,
+        },
+        Err(e) => return Err(e),
+    };
+    filter.apply(&mut data, fetch).await;
+
+    if let Layout::Legacy { prefix } = layout {
+        return Ok(crate::legacy::render(LegacyUserTemplate {
+            prefix,
+            username,
+            about,
+            deleted,
+            trophies: trophies.unwrap_or_default(),
+            data,
+            uri,
+        }));
+    }
+
+    Ok(UserTemplate {
+        username,
+        about,
+        deleted,
+        trophies: trophies.unwrap_or_default(),
+        data,
+        uri,
+        theme,
+    }
+    .into_response())
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -45,5 +139,4 @@ pub struct UserParams {
     sort: Option<UserSortingMode>,
     filter: Option<UserFilterMode>,
     t: Option<SearchTimeOrdering>,
-    after: Option<String>,
 }
\ No newline at end of file
@@ -1,7 +1,246 @@
+/// Environment variable pointing the server at another upstream than Reddit,
+/// like `http://127.0.0.1:8081` for the mock the tests run against.
+const UPSTREAM_VAR: &str = "OLDER_REDDIT_UPSTREAM";
+
 pub fn get_reddit_domain() -> url_builder::URLBuilder {
     let mut a = url_builder::URLBuilder::new();
 
-    a.set_protocol("https").set_host("pay.reddit.com");
+    let upstream = std::env::var(UPSTREAM_VAR)
+        .ok()
+        .and_then(|u| reqwest::Url::parse(&u).ok());
+
+    match upstream {
+        Some(url) => {
+            a.set_protocol(url.scheme())
+                .set_host(url.host_str().unwrap_or_default());
+            if let Some(port) = url.port() {
+                a.set_port(port);
+            }
+        }
+        None => {
+            a.set_protocol("https").set_host("pay.reddit.com");
+        }
+    }
 
     a
 }
+
+/// Whether `name` can be a subreddit, as Reddit allows them: 1 to 21 letters, digits or underscores.
+/// Typed names are checked with this before they end up in a URL or a response header.
+pub fn is_subreddit_name(name: &str) -> bool {
+    (1..=21).contains(&name.len())
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+/// Reddit hosts whose links can be served by us instead.
+const REDDIT_HOSTS: [&str; 5] = [
+    "www.reddit.com",
+    "old.reddit.com",
+    "new.reddit.com",
+    "np.reddit.com",
+    "reddit.com",
+];
+
+/// Makes absolute links to Reddit relative, so they stay on this server,
+/// as our routes mirror Reddit's.
+pub fn rewrite_reddit_links(text: &str) -> String {
+    let mut text = text.to_string();
+
+    for host in REDDIT_HOSTS {
+        for protocol in ["https://", "http://"] {
+            text = text.replace(&format!("{}{}/", protocol, host), "/");
+        }
+    }
+
+    text
+}
+
+pub fn format_timestamp(timestamp: &u64) -> String {
+    match chrono::DateTime::from_timestamp(*timestamp as i64, 0) {
+        Some(t) => t.format("%Y-%m-%d %H:%M UTC").to_string(),
+        None => String::from("[time]"),
+    }
+}
+
+/// Whether `path` stays on this server, for redirects coming from forms.
+/// Only visible ASCII is allowed, anything else can't go in a `Location` header as it is.
+pub fn is_local_path(path: &str) -> bool {
+    path.starts_with('/')
+        && !path.starts_with("//")
+        && !path.starts_with("/\\")
+        && path.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// The current page with `key` set to `value` in its query, other parameters kept.
+pub fn with_param(uri: &axum::http::Uri, key: &str, value: &str) -> String {
+    let mut url = reqwest::Url::parse("http://localhost/").expect("valid URL");
+    url.set_path(uri.path());
+    url.set_query(uri.query());
+
+    let params: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| k != key)
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(params)
+        .append_pair(key, value);
+
+    format!("{}?{}", url.path(), url.query().unwrap_or_default())
+}
+
+/// Stand in for `>!` and `!<` while the text goes through Markdown,
+/// which would take the `>` for a quote. From Unicode's private use area.
+const SPOILER_START: char = '\u{E000}';
+const SPOILER_END: char = '\u{E001}';
+
+/// Same as the `markdown` filter, with Reddit's `>!inline spoilers!<` hidden until clicked.
+pub fn markdown(text: &str) -> String {
+    let text = text.replace([SPOILER_START, SPOILER_END], "");
+    let mut marked = String::with_capacity(text.len());
+
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            marked.push('\n');
+        }
+        mark_spoilers(line, &mut marked);
+    }
+
+    let html = match askama::filters::markdown(askama::Html, marked, None) {
+        Ok(m) => m.to_string(),
+        Err(_) => return String::new(),
+    };
+
+    html.replace(SPOILER_START, r#"<span class="spoiler-text" tabindex="0">"#)
+        .replace(SPOILER_END, "</span>")
+}
+
+/// Spoilers don't span lines on Reddit, an opening `>!` without its `!<` is left as it is.
+fn mark_spoilers(line: &str, out: &mut String) {
+    let mut rest = line;
+
+    while let Some(start) = rest.find(">!") {
+        let inner = &rest[start + 2..];
+        let Some(end) = inner.find("!<") else {
+            break;
+        };
+
+        out.push_str(&rest[..start]);
+        out.push(SPOILER_START);
+        out.push_str(&inner[..end]);
+        out.push(SPOILER_END);
+        rest = &inner[end + 2..];
+    }
+
+    out.push_str(rest);
+}
+
+/// Wraps plain text to `width` columns, each line starting with `indent`.
+/// Code blocks, between lines starting with ```` ``` ````, are kept as they are.
+pub fn wrap_text(text: &str, indent: &str, width: usize) -> Vec<String> {
+    let width = width.saturating_sub(indent.len()).max(20);
+    let mut lines = Vec::new();
+    let mut in_code = false;
+
+    for line in text.lines() {
+        if line.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+
+        if in_code {
+            lines.push(format!("{}{}", indent, line));
+        } else {
+            for wrapped in wrap(line, width) {
+                lines.push(format!("{}{}", indent, wrapped));
+            }
+        }
+    }
+
+    lines
+}
+
+/// Splits a line at spaces into lines of at most `width` characters.
+/// Words longer than that, like URLs, get a line of their own.
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+
+    for word in line.split_whitespace() {
+        let len = current.chars().count();
+        if len > 0 && len + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+
+    // keeps blank lines between paragraphs
+    lines.push(current);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subreddit_names() {
+        assert!(is_subreddit_name("rust"));
+        assert!(is_subreddit_name("Ask_Reddit2"));
+        assert!(!is_subreddit_name(""));
+        assert!(!is_subreddit_name("a_name_far_too_long_22"));
+        assert!(!is_subreddit_name("foo\r\n20 text/gemini"));
+        assert!(!is_subreddit_name("../settings"));
+        assert!(!is_subreddit_name("a+b"));
+    }
+
+    #[test]
+    fn local_paths() {
+        assert!(is_local_path("/r/rust/comments/1abcde?sorting=top"));
+        assert!(is_local_path("/lite/r/rust"));
+        assert!(!is_local_path("https://evil.example"));
+        assert!(!is_local_path("//evil.example"));
+        assert!(!is_local_path("/\\evil.example"));
+        assert!(!is_local_path("/x\r\nSet-Cookie: a=b"));
+        assert!(!is_local_path("/r/caf\u{e9}"));
+        assert!(!is_local_path("/a b"));
+    }
+
+    #[test]
+    fn params_keep_the_query() {
+        let uri: axum::http::Uri = "/r/rust/comments/1abcde?sorting=top&spoiler=false"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            with_param(&uri, "spoiler", "true"),
+            "/r/rust/comments/1abcde?sorting=top&spoiler=true"
+        );
+
+        let uri: axum::http::Uri = "/r/rust/comments/1abcde".parse().unwrap();
+        assert_eq!(
+            with_param(&uri, "spoiler", "true"),
+            "/r/rust/comments/1abcde?spoiler=true"
+        );
+    }
+
+    #[test]
+    fn inline_spoilers_are_hidden() {
+        let html = markdown("It was >!the butler!<, and >!*him* again!<.");
+        assert_eq!(
+            html.trim(),
+            "<p>It was <span class=\"spoiler-text\" tabindex=\"0\">the butler</span>, \
+             and <span class=\"spoiler-text\" tabindex=\"0\"><em>him</em> again</span>.</p>"
+        );
+
+        let html = markdown(">! not closed\n\nclosed !<");
+        assert!(!html.contains("spoiler-text"), "{}", html);
+        assert!(markdown("> quoted").contains("<blockquote>"));
+        assert!(!markdown("<b>>!raw!<</b>").contains("<b>"));
+    }
+}
@@ -1,27 +1,228 @@
 use askama::Template;
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     headers::UserAgent,
-    http::StatusCode,
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
     TypedHeader,
 };
-use reqwest::Client;
+use serde::Deserialize;
 
-use crate::api_result_types::WikiPageData;
+use crate::{
+    api::WikiRevisionsQuery,
+    api_result_types::{WikiPageData, WikiPageListingData},
+    legacy::Layout,
+    pagination::PageRequest,
+    themes::CurrentTheme,
+    upstream::Client,
+};
 
 #[derive(Template)]
 #[template(path = "wiki.html")]
 pub struct WikiTemplate {
     subreddit: String,
+    page: String,
+    revision: Option<String>,
     data: WikiPageData,
+    theme: CurrentTheme,
 }
 
-pub async fn wiki_page(
+#[derive(Template)]
+#[template(path = "wiki_pages.html")]
+pub struct WikiPagesTemplate {
+    subreddit: String,
+    data: WikiPageListingData,
+    theme: CurrentTheme,
+}
+
+#[derive(Template)]
+#[template(path = "wiki_revisions.html")]
+pub struct WikiRevisionsTemplate {
+    subreddit: String,
+    page: Option<String>,
+    data: WikiRevisionsQuery,
+    uri: Uri,
+    theme: CurrentTheme,
+}
+
+#[derive(Template)]
+#[template(path = "legacy/wiki.html")]
+pub struct LegacyWikiTemplate {
+    prefix: &'static str,
+    subreddit: String,
+    page: String,
+    revision: Option<String>,
+    data: WikiPageData,
+    uri: Uri,
+}
+
+#[derive(Template)]
+#[template(path = "legacy/wiki_pages.html")]
+pub struct LegacyWikiPagesTemplate {
+    prefix: &'static str,
+    subreddit: String,
+    data: WikiPageListingData,
+    uri: Uri,
+}
+
+#[derive(Template)]
+#[template(path = "legacy/wiki_revisions.html")]
+pub struct LegacyWikiRevisionsTemplate {
+    prefix: &'static str,
+    subreddit: String,
+    page: Option<String>,
+    data: WikiRevisionsQuery,
+    uri: Uri,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn wiki_index(
     Path(subreddit): Path<String>,
+    Query(params): Query<WikiParams>,
+    Query(page): Query<PageRequest>,
+    TypedHeader(user_agent): TypedHeader<UserAgent>,
+    State(client): State<Client>,
+    uri: Uri,
+    theme: CurrentTheme,
+    layout: Layout,
+) -> Result<Response, StatusCode> {
+    wiki(
+        &client,
+        subreddit,
+        "index",
+        params,
+        page,
+        user_agent.as_str(),
+        uri,
+        theme,
+        layout,
+    )
+    .await
+}
+
+/// Handles everything under `/r/:subreddit/wiki/`, just like Reddit does:
+/// `pages` is the page index, `revisions/*page` the history, anything else is a page.
+#[allow(clippy::too_many_arguments)]
+pub async fn wiki_page(
+    Path((subreddit, path)): Path<(String, String)>,
+    Query(params): Query<WikiParams>,
+    Query(page): Query<PageRequest>,
     TypedHeader(user_agent): TypedHeader<UserAgent>,
     State(client): State<Client>,
-) -> Result<WikiTemplate, StatusCode> {
-    let data = crate::api::wiki(&client, &subreddit, None, user_agent.as_str()).await?;
+    uri: Uri,
+    theme: CurrentTheme,
+    layout: Layout,
+) -> Result<Response, StatusCode> {
+    wiki(
+        &client,
+        subreddit,
+        path.trim_matches('/'),
+        params,
+        page,
+        user_agent.as_str(),
+        uri,
+        theme,
+        layout,
+    )
+    .await
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn wiki(
+    client: &Client,
+    subreddit: String,
+    path: &str,
+    params: WikiParams,
+    page: PageRequest,
+    user_agent: &str,
+    uri: Uri,
+    theme: CurrentTheme,
+    layout: Layout,
+) -> Result<Response, StatusCode> {
+    if path == "pages" {
+        let data = crate::api::wiki_pages(client, &subreddit, user_agent).await?;
+
+        if let Layout::Legacy { prefix } = layout {
+            return Ok(crate::legacy::render(LegacyWikiPagesTemplate {
+                prefix,
+                subreddit,
+                data,
+                uri,
+            }));
+        }
+
+        return Ok(WikiPagesTemplate {
+            subreddit,
+            data,
+            theme,
+        }
+        .into_response());
+    }
+
+    if path == "revisions" || path.starts_with("revisions/") {
+        let path = path
+            .strip_prefix("revisions/")
+            .map(|p| p.trim_start_matches('/'))
+            .filter(|p| !p.is_empty())
+            .map(str::to_string);
+
+        let data =
+            crate::api::wiki_revisions(client, &subreddit, path.as_deref(), &page, user_agent)
+                .await?;
+
+        if let Layout::Legacy { prefix } = layout {
+            return Ok(crate::legacy::render(LegacyWikiRevisionsTemplate {
+                prefix,
+                subreddit,
+                page: path,
+                data,
+                uri,
+            }));
+        }
+
+        return Ok(WikiRevisionsTemplate {
+            subreddit,
+            page: path,
+            data,
+            uri,
+            theme,
+        }
+        .into_response());
+    }
+
+    let mut data = crate::api::wiki(
+        client,
+        &subreddit,
+        Some(path),
+        params.v.as_deref(),
+        user_agent,
+    )
+    .await?;
+    data.content_md = crate::utils::rewrite_reddit_links(&data.content_md);
+
+    if let Layout::Legacy { prefix } = layout {
+        return Ok(crate::legacy::render(LegacyWikiTemplate {
+            prefix,
+            subreddit,
+            page: path.to_string(),
+            revision: params.v,
+            data,
+            uri,
+        }));
+    }
+
+    Ok(WikiTemplate {
+        subreddit,
+        page: path.to_string(),
+        revision: params.v,
+        data,
+        theme,
+    }
+    .into_response())
+}
 
-    Ok(WikiTemplate { subreddit, data })
+#[derive(Debug, Clone, Deserialize)]
+pub struct WikiParams {
+    /// Revision id
+    v: Option<String>,
 }
@@ -0,0 +1,55 @@
+mod common;
+
+use axum::http::StatusCode;
+use serde_json::Value;
+
+fn json(body: &str) -> Value {
+    serde_json::from_str(body).unwrap()
+}
+
+#[tokio::test]
+async fn listings_page_with_their_tokens() {
+    let app = common::app().await;
+    let first = json(&app.get("/api/v1/r/rust").await.body);
+    let next = first["next"].as_str().unwrap();
+
+    let page = app.get(&format!("/api/v1/r/rust?page={}", next)).await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    let second = json(&page.body);
+    assert_eq!(second["items"][0]["id"], "1page2");
+    assert!(second["previous"].is_string());
+}
+
+#[tokio::test]
+async fn made_up_page_tokens_are_refused() {
+    let app = common::app().await;
+    let page = app.get("/api/v1/r/rust?page=t3_1abcde").await;
+
+    assert_eq!(page.status, StatusCode::BAD_REQUEST);
+    assert_eq!(json(&page.body)["error"], "Bad Request");
+}
+
+#[tokio::test]
+async fn threads_nest_their_replies() {
+    let app = common::app().await;
+    let page = app.get("/api/v1/r/rust/comments/1abcde").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    let thread = json(&page.body);
+    assert_eq!(thread["post"]["title"], "Announcing Rust 1.73.0");
+
+    let reply = &thread["comments"][0]["replies"][0];
+    assert_eq!(reply["author"], "ferris_fan");
+    assert_eq!(reply["is_op"], true);
+    assert_eq!(reply["replies"][0]["id"], "k6c0003");
+}
+
+#[tokio::test]
+async fn reddit_errors_keep_their_status() {
+    let app = common::app().await;
+    let page = app.get("/api/v1/r/bannedsub").await;
+
+    assert_eq!(page.status, StatusCode::NOT_FOUND);
+    assert_eq!(json(&page.body)["error"], "Not Found");
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use axum::http::{header, StatusCode};
+
+#[tokio::test]
+async fn thread_shows_post_and_nested_comments() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/comments/1abcde").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("<h2 class=\"post-title\">Announcing Rust 1.73.0</h2>");
+    page.assert_contains("<strong>1.73.0</strong>");
+    page.assert_contains("Thanks to everyone who contributed!");
+    page.assert_contains("The new panic messages are <em>so</em> much nicer.");
+    page.assert_contains("<code>Option::is_some_and</code>");
+    page.assert_contains("Is <code>LocalKey&lt;Cell&gt;</code> stable now?");
+}
+
+#[tokio::test]
+async fn thread_skips_more_objects() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/comments/1abcde").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_not_contains("k6c0006");
+    page.assert_not_contains("k6c0009");
+}
+
+#[tokio::test]
+async fn thread_marks_op_and_flairs() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/comments/1abcde").await;
+
+    page.assert_contains("<div class=\"op\">OP</div>");
+    page.assert_contains(">core team</div>");
+}
+
+#[tokio::test]
+async fn gallery_shows_one_image_at_a_time() {
+    let app = common::app().await;
+
+    let page = app.get("/r/rust/comments/1gal01").await;
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("Image 1/3");
+    page.assert_contains("/i/k3h8zq5v1ftb1.jpg");
+
+    let page = app.get("/r/rust/comments/1gal01?gallery_index=2").await;
+    page.assert_contains("Image 3/3");
+    page.assert_contains("/i/a9c4xq5v1ftb1.jpg");
+}
+
+#[tokio::test]
+async fn poll_shows_options_and_votes() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/comments/1poll1").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("Votes (total: 1873)");
+    page.assert_contains("Neovim");
+    page.assert_contains("RustRover");
+}
+
+#[tokio::test]
+async fn short_links_redirect_to_the_thread() {
+    let app = common::app().await;
+    let (status, location) = app.location("/comments/1abcde").await;
+
+    assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(location, "/r/rust/comments/1abcde");
+}
+
+#[tokio::test]
+async fn share_links_redirect_to_the_thread() {
+    let app = common::app().await;
+    let (status, location) = app.location("/r/rust/s/Xk2Lq9vB3d").await;
+
+    // the query Reddit tracks shares with is left out
+    assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(location, "/r/rust/comments/1abcde");
+}
+
+#[tokio::test]
+async fn share_codes_keep_their_case() {
+    let app = common::app().await;
+
+    // subreddit names don't, the code is still found
+    let (status, location) = app.location("/r/Rust/s/Xk2Lq9vB3d").await;
+    assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(location, "/r/rust/comments/1abcde");
+
+    let (status, location) = app.location("/r/rust/s/xk2lq9vb3d").await;
+    assert_ne!(status, StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(location, "");
+}
+
+#[tokio::test]
+async fn share_links_only_lead_to_threads() {
+    let app = common::app().await;
+    let (status, location) = app.location("/r/rust/s/offsite").await;
+
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(location, "");
+}
+
+#[tokio::test]
+async fn thread_feeds_list_its_comments() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/comments/1abcde.rss").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    assert_eq!(page.headers[header::CONTENT_TYPE], "application/rss+xml");
+    page.assert_contains("<guid isPermaLink=\"false\">t1_k6c0001</guid>");
+    page.assert_contains("<guid isPermaLink=\"false\">t1_k6c0004</guid>");
+}
+
+#[tokio::test]
+async fn markdown_export_is_a_download() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/comments/1abcde?format=md").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("# Announcing Rust 1.73.0");
+    page.assert_contains("> **u/ferris_fan**");
+}
+
+#[tokio::test]
+async fn text_export_is_plain_text() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/comments/1abcde?format=txt").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    assert_eq!(
+        page.headers[header::CONTENT_DISPOSITION],
+        "attachment; filename=\"rust-1abcde.txt\""
+    );
+    page.assert_contains(
+        "Read the release notes\n\
+         <https://blog.rust-lang.org/2023/10/05/Rust-1.73.0.html> & have fun!",
+    );
+    page.assert_contains("    u/ferris_fan - ");
+    page.assert_not_contains("=> ");
+}
+
+#[tokio::test]
+async fn epub_export_is_a_zip_led_by_its_mimetype() {
+    let app = common::app().await;
+    let (status, headers, body) = app.download("/r/rust/comments/1abcde?format=epub").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers[header::CONTENT_TYPE], "application/epub+zip");
+
+    let mut epub = zip::ZipArchive::new(std::io::Cursor::new(body)).unwrap();
+    let names: Vec<_> = epub.file_names().collect();
+    for name in [
+        "META-INF/container.xml",
+        "OEBPS/content.opf",
+        "OEBPS/thread.xhtml",
+    ] {
+        assert!(names.contains(&name), "{} missing from {:?}", name, names);
+    }
+
+    let mimetype = epub.by_index(0).unwrap();
+    assert_eq!(mimetype.name(), "mimetype");
+    assert_eq!(mimetype.compression(), zip::CompressionMethod::Stored);
+    drop(mimetype);
+
+    let mut thread = String::new();
+    std::io::Read::read_to_string(
+        &mut epub.by_name("OEBPS/thread.xhtml").unwrap(),
+        &mut thread,
+    )
+    .unwrap();
+    assert!(thread.contains("Announcing Rust 1.73.0"));
+    assert!(thread.contains("Thanks to everyone who contributed!"));
+}
+
+#[tokio::test]
+async fn spoilers_are_hidden_until_asked_for() {
+    let app = common::app().await;
+    let page = app.get("/r/crabs/comments/1spo01?sorting=top").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_not_contains("It's a Christmas tree.");
+    page.assert_contains("/r/crabs/comments/1spo01?sorting=top&amp;spoiler=true");
+    page.assert_contains(r#"<span class="spoiler-text" tabindex="0">the tree</span>"#);
+    page.assert_not_contains("&gt;!");
+
+    let page = app
+        .get("/r/crabs/comments/1spo01?sorting=top&spoiler=true")
+        .await;
+    page.assert_contains("It's a Christmas tree.");
+}
@@ -0,0 +1,303 @@
+#![allow(dead_code)] // every test binary uses a different part
+
+// Runs the server against a mock of Reddit serving the fixtures in `tests/fixtures`.
+//
+// The fixtures are a snapshot store, like `--record` writes,
+// and are recorded again from Reddit with `cargo run --example record_fixtures`.
+
+use std::{
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    sync::OnceLock,
+};
+
+use axum::{
+    http::{header, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use older_reddit::{routes, state::AppState, upstream::Client};
+
+/// What Reddit answers for subreddits that can't be shown.
+/// Kept here, as the store only holds successful responses.
+const ERRORS: &[(&str, StatusCode, &str)] = &[
+    (
+        "/r/privatesub/",
+        StatusCode::FORBIDDEN,
+        r#"{"reason": "private", "message": "Forbidden", "error": 403}"#,
+    ),
+    (
+        "/r/bannedsub/",
+        StatusCode::NOT_FOUND,
+        r#"{"reason": "banned", "message": "Not Found", "error": 404}"#,
+    ),
+    (
+        "/r/ratelimited/",
+        StatusCode::TOO_MANY_REQUESTS,
+        r#"{"message": "Too Many Requests", "error": 429}"#,
+    ),
+    (
+        "/user/flaky/about",
+        StatusCode::TOO_MANY_REQUESTS,
+        r#"{"message": "Too Many Requests", "error": 429}"#,
+    ),
+    (
+        "/r/overloaded/",
+        StatusCode::SERVICE_UNAVAILABLE,
+        "<html><body>all of our servers are busy right now</body></html>",
+    ),
+];
+
+/// Sent with every answer, like Reddit does.
+pub const RATELIMIT: [(&str, &str); 3] = [
+    ("x-ratelimit-remaining", "598.0"),
+    ("x-ratelimit-used", "2"),
+    ("x-ratelimit-reset", "412"),
+];
+
+pub fn fixtures() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// The mock is shared by every test of a binary,
+/// since the server finds it through an environment variable.
+fn mock_upstream() -> SocketAddr {
+    static ADDR: OnceLock<SocketAddr> = OnceLock::new();
+
+    *ADDR.get_or_init(|| {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // tests each have their own runtime, which stops with them
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+
+            runtime.block_on(async {
+                let store = Client::offline(fixtures());
+                let app = Router::new().fallback(move |uri: Uri| replay(store.clone(), uri));
+
+                axum::Server::from_tcp(listener)
+                    .unwrap()
+                    .serve(app.into_make_service())
+                    .await
+                    .unwrap();
+            });
+        });
+
+        std::env::set_var("OLDER_REDDIT_UPSTREAM", format!("http://{}", addr));
+        addr
+    })
+}
+
+async fn replay(store: Client, uri: Uri) -> Response {
+    let path = uri.path().to_lowercase();
+
+    for (prefix, status, body) in ERRORS {
+        if path.starts_with(prefix) {
+            return (*status, *body).into_response();
+        }
+    }
+
+    let url = format!("http://reddit.test{}", uri);
+    let response = match store.get(&url, "tests").await {
+        Ok(r) if r.status().is_redirection() => {
+            let location = r.location().unwrap_or_default().to_string();
+            (r.status(), [(header::LOCATION, location)]).into_response()
+        }
+        Ok(r) => (r.status(), r.bytes()).into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    (RATELIMIT, response).into_response()
+}
+
+/// The server, listening on a port of its own.
+pub struct TestApp {
+    addr: SocketAddr,
+    http: reqwest::Client,
+}
+
+/// A page as a browser would get it.
+pub struct Page {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl Page {
+    async fn from(response: reqwest::Response) -> Page {
+        Page {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.text().await.unwrap(),
+        }
+    }
+
+    /// Fails with the page in the message, which is easier to debug than a bare assertion.
+    #[track_caller]
+    pub fn assert_contains(&self, text: &str) {
+        assert!(
+            self.body.contains(text),
+            "expected {:?} in the page:\n{}",
+            text,
+            self.body
+        );
+    }
+
+    /// The cookie the page sets, as `name=value`.
+    #[track_caller]
+    pub fn cookie(&self) -> String {
+        let cookie = self
+            .headers
+            .get(header::SET_COOKIE)
+            .expect("no cookie set")
+            .to_str()
+            .unwrap();
+
+        cookie.split(';').next().unwrap_or_default().to_string()
+    }
+
+    #[track_caller]
+    pub fn assert_not_contains(&self, text: &str) {
+        assert!(
+            !self.body.contains(text),
+            "didn't expect {:?} in the page:\n{}",
+            text,
+            self.body
+        );
+    }
+}
+
+pub async fn app() -> TestApp {
+    mock_upstream();
+
+    let state = AppState::from_env(Client::new()).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = axum::Server::from_tcp(listener)
+        .unwrap()
+        .serve(routes::router(state).into_make_service());
+    tokio::spawn(server);
+
+    TestApp {
+        addr,
+        http: reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap(),
+    }
+}
+
+impl TestApp {
+    pub async fn get(&self, path: &str) -> Page {
+        let response = self
+            .http
+            .get(format!("http://{}{}", self.addr, path))
+            .header(header::USER_AGENT, "older-reddit tests")
+            .send()
+            .await
+            .unwrap();
+
+        Page::from(response).await
+    }
+
+    /// Submits a form, as a browser would from one of our pages.
+    pub async fn post(&self, path: &str, form: &[(&str, &str)]) -> Page {
+        self.post_from(&format!("http://{}", self.addr), path, form)
+            .await
+    }
+
+    /// Submits a form from a page of `origin`.
+    pub async fn post_from(&self, origin: &str, path: &str, form: &[(&str, &str)]) -> Page {
+        let response = self
+            .http
+            .post(format!("http://{}{}", self.addr, path))
+            .header(header::USER_AGENT, "older-reddit tests")
+            .header(header::ORIGIN, origin)
+            .form(form)
+            .send()
+            .await
+            .unwrap();
+
+        Page::from(response).await
+    }
+
+    /// Submits a form as browsers that send neither `Origin` nor `Referer` do.
+    pub async fn post_without_origin(&self, path: &str, form: &[(&str, &str)]) -> Page {
+        let response = self
+            .http
+            .post(format!("http://{}{}", self.addr, path))
+            .header(header::USER_AGENT, "older-reddit tests")
+            .form(form)
+            .send()
+            .await
+            .unwrap();
+
+        Page::from(response).await
+    }
+
+    /// Opens `path` with a cookie, `name=value` as [`Page::cookie`] gives it.
+    pub async fn get_with_cookie(&self, path: &str, cookie: &str) -> Page {
+        let response = self
+            .http
+            .get(format!("http://{}{}", self.addr, path))
+            .header(header::USER_AGENT, "older-reddit tests")
+            .header(header::COOKIE, cookie)
+            .send()
+            .await
+            .unwrap();
+
+        Page::from(response).await
+    }
+
+    /// Opens `path` with extra headers, like a proxy in front of the server would add.
+    pub async fn get_with_headers(&self, path: &str, headers: &[(&str, &str)]) -> Page {
+        let mut request = self
+            .http
+            .get(format!("http://{}{}", self.addr, path))
+            .header(header::USER_AGENT, "older-reddit tests");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        Page::from(request.send().await.unwrap()).await
+    }
+
+    /// A file that isn't text, like an EPUB, with its status and headers.
+    pub async fn download(&self, path: &str) -> (StatusCode, HeaderMap, Vec<u8>) {
+        let response = self
+            .http
+            .get(format!("http://{}{}", self.addr, path))
+            .header(header::USER_AGENT, "older-reddit tests")
+            .send()
+            .await
+            .unwrap();
+
+        let (status, headers) = (response.status(), response.headers().clone());
+        (status, headers, response.bytes().await.unwrap().to_vec())
+    }
+
+    /// Where a redirect leads.
+    pub async fn location(&self, path: &str) -> (StatusCode, String) {
+        let response = self
+            .http
+            .get(format!("http://{}{}", self.addr, path))
+            .header(header::USER_AGENT, "older-reddit tests")
+            .send()
+            .await
+            .unwrap();
+
+        let location = response
+            .headers()
+            .get(header::LOCATION)
+            .and_then(|l| l.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        (response.status(), location)
+    }
+}
@@ -0,0 +1,57 @@
+mod common;
+
+use axum::http::StatusCode;
+
+#[tokio::test]
+async fn private_subreddits_are_forbidden() {
+    let app = common::app().await;
+
+    assert_eq!(app.get("/r/privatesub").await.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn banned_subreddits_are_not_found() {
+    let app = common::app().await;
+
+    assert_eq!(app.get("/r/bannedsub").await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn reddit_being_down_is_passed_on() {
+    let app = common::app().await;
+
+    assert_eq!(
+        app.get("/r/overloaded").await.status,
+        StatusCode::SERVICE_UNAVAILABLE
+    );
+}
+
+#[tokio::test]
+async fn unreadable_responses_are_server_errors() {
+    let app = common::app().await;
+
+    assert_eq!(
+        app.get("/r/broken").await.status,
+        StatusCode::INTERNAL_SERVER_ERROR
+    );
+}
+
+#[tokio::test]
+async fn missing_threads_are_not_found() {
+    let app = common::app().await;
+
+    assert_eq!(
+        app.get("/r/rust/comments/zzzzzz").await.status,
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[tokio::test]
+async fn api_errors_are_json() {
+    let app = common::app().await;
+    let page = app.get("/api/v1/r/privatesub").await;
+
+    assert_eq!(page.status, StatusCode::FORBIDDEN);
+    let json: serde_json::Value = serde_json::from_str(&page.body).unwrap();
+    assert!(json["error"].is_string());
+}
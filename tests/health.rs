@@ -0,0 +1,67 @@
+mod common;
+
+use axum::http::StatusCode;
+
+async fn readiness(app: &common::TestApp) -> (StatusCode, serde_json::Value) {
+    let page = app.get("/readyz").await;
+    let report = serde_json::from_str(&page.body).unwrap();
+    (page.status, report)
+}
+
+#[tokio::test]
+async fn healthz_answers() {
+    let app = common::app().await;
+    let page = app.get("/healthz").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    assert_eq!(page.body, "ok");
+}
+
+#[tokio::test]
+async fn readyz_probes_reddit_when_idle() {
+    let app = common::app().await;
+    let (status, report) = readiness(&app).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", report);
+    assert_eq!(report["status"], "ok", "{}", report);
+    assert_eq!(report["upstream"]["reachable"], true, "{}", report);
+    assert_eq!(report["ratelimit"]["remaining"], 598.0, "{}", report);
+    assert_eq!(
+        report["caches"]["share_links"]["capacity"], 4096,
+        "{}",
+        report
+    );
+}
+
+#[tokio::test]
+async fn readyz_is_degraded_when_reddit_rate_limits() {
+    let app = common::app().await;
+    app.get("/r/ratelimited").await;
+
+    let (status, report) = readiness(&app).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", report);
+    assert_eq!(report["status"], "degraded", "{}", report);
+    assert_eq!(report["upstream"]["last_status"], 429, "{}", report);
+}
+
+#[tokio::test]
+async fn readyz_is_degraded_when_reddit_fails() {
+    let app = common::app().await;
+    app.get("/r/overloaded").await;
+
+    let (_, report) = readiness(&app).await;
+
+    assert_eq!(report["status"], "degraded", "{}", report);
+    assert_eq!(report["upstream"]["last_status"], 503, "{}", report);
+}
+
+#[tokio::test]
+async fn readyz_counts_cached_share_links() {
+    let app = common::app().await;
+    app.location("/comments/1abcde").await;
+
+    let (_, report) = readiness(&app).await;
+
+    assert_eq!(report["caches"]["share_links"]["entries"], 1, "{}", report);
+}
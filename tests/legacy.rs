@@ -0,0 +1,54 @@
+mod common;
+
+use axum::http::{header, StatusCode};
+
+/// Every page under `/lite` is rendered with the legacy templates: no CSS, no `<div>`s.
+#[tokio::test]
+async fn every_page_has_a_legacy_layout() {
+    let app = common::app().await;
+
+    for path in [
+        "/lite/r/rust",
+        "/lite/r/rust/about",
+        "/lite/r/rust/comments/1abcde",
+        "/lite/r/rust/comments/1nsfw9",
+        "/lite/r/rust/search?q=async",
+        "/lite/r/rust/wiki",
+        "/lite/r/rust/wiki/pages",
+        "/lite/r/rust/wiki/revisions",
+        "/lite/u/spez",
+        "/lite/settings",
+        "/lite/subscriptions",
+    ] {
+        let page = app.get(path).await;
+
+        assert_eq!(page.status, StatusCode::OK, "{}", path);
+        page.assert_contains("<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 3.2 Final//EN\">");
+        page.assert_not_contains("<div");
+        page.assert_not_contains("stylesheet");
+    }
+}
+
+#[tokio::test]
+async fn legacy_forms_stay_under_lite() {
+    let app = common::app().await;
+
+    let page = app.get("/lite/settings").await;
+    page.assert_contains("action=\"/lite/settings\"");
+
+    let page = app.get("/lite/r/rust/comments/1nsfw9").await;
+    page.assert_contains("action=\"/lite/nsfw\"");
+    page.assert_contains("value=\"/lite/r/rust/comments/1nsfw9\"");
+
+    let form = [
+        ("sort", "suggested"),
+        ("comment_sort", "suggested"),
+        ("search_sort", "relevance"),
+        ("search_time", "all"),
+        ("image_size", "medium"),
+        ("comment_depth", "0"),
+    ];
+    let page = app.post("/lite/settings", &form).await;
+    assert_eq!(page.status, StatusCode::SEE_OTHER);
+    assert_eq!(page.headers[header::LOCATION], "/lite/settings");
+}
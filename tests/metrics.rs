@@ -0,0 +1,87 @@
+mod common;
+
+use axum::http::StatusCode;
+
+/// The value of a series in `/metrics`, given as it is written there, labels sorted.
+fn value(metrics: &str, series: &str) -> Option<f64> {
+    metrics
+        .lines()
+        .find_map(|l| l.strip_prefix(series)?.strip_prefix(' '))
+        .and_then(|v| v.parse().ok())
+}
+
+#[tokio::test]
+async fn requests_are_counted_by_route() {
+    let app = common::app().await;
+    app.get("/r/rust").await;
+    app.get("/r/rust/comments/1abcde").await;
+
+    let page = app.get("/metrics").await;
+    assert_eq!(page.status, StatusCode::OK);
+
+    let series =
+        r#"older_reddit_http_requests_total{method="GET",route="/r/:subreddit",status="200"}"#;
+    assert!(value(&page.body, series) >= Some(1.0), "{}", page.body);
+    page.assert_contains(
+        r#"older_reddit_http_request_duration_seconds_count{method="GET",route="/r/:subreddit/comments/:file"}"#,
+    );
+}
+
+#[tokio::test]
+async fn upstream_requests_are_counted_by_endpoint() {
+    let app = common::app().await;
+    app.get("/r/rust/comments/1abcde").await;
+    app.get("/r/privatesub").await;
+
+    let page = app.get("/metrics").await;
+
+    let ok = r#"older_reddit_upstream_requests_total{endpoint="comments",status="200"}"#;
+    let forbidden = r#"older_reddit_upstream_requests_total{endpoint="subreddit",status="403"}"#;
+    assert!(value(&page.body, ok) >= Some(1.0), "{}", page.body);
+    assert!(value(&page.body, forbidden) >= Some(1.0), "{}", page.body);
+    page.assert_contains(
+        r#"older_reddit_upstream_request_duration_seconds_count{endpoint="comments"}"#,
+    );
+}
+
+#[tokio::test]
+async fn rate_limit_is_read_from_reddits_answers() {
+    let app = common::app().await;
+    app.get("/r/rust").await;
+
+    let page = app.get("/metrics").await;
+
+    assert_eq!(
+        value(&page.body, "older_reddit_upstream_ratelimit_remaining"),
+        Some(598.0)
+    );
+    assert_eq!(
+        value(&page.body, "older_reddit_upstream_ratelimit_reset_seconds"),
+        Some(412.0)
+    );
+}
+
+#[tokio::test]
+async fn decode_failures_are_counted_by_endpoint() {
+    let app = common::app().await;
+    app.get("/r/broken").await;
+
+    let page = app.get("/metrics").await;
+
+    let series = r#"older_reddit_decode_failures_total{endpoint="subreddit"}"#;
+    assert!(value(&page.body, series) >= Some(1.0), "{}", page.body);
+}
+
+#[tokio::test]
+async fn share_link_cache_hits_are_counted() {
+    let app = common::app().await;
+    app.location("/comments/1abcde").await;
+    app.location("/comments/1abcde").await;
+
+    let page = app.get("/metrics").await;
+
+    let hits = r#"older_reddit_cache_lookups_total{cache="share_links",result="hit"}"#;
+    let misses = r#"older_reddit_cache_lookups_total{cache="share_links",result="miss"}"#;
+    assert!(value(&page.body, hits) >= Some(1.0), "{}", page.body);
+    assert!(value(&page.body, misses) >= Some(1.0), "{}", page.body);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use axum::http::{header, StatusCode};
+
+const THREAD: &str = "/r/rust/comments/1nsfw9";
+
+#[tokio::test]
+async fn nsfw_threads_ask_first() {
+    let app = common::app().await;
+    let page = app.get(THREAD).await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("This page is marked as NSFW");
+    page.assert_contains(&format!("value=\"{}\"", THREAD));
+    page.assert_not_contains("unsafe_enjoyer");
+}
+
+#[tokio::test]
+async fn consent_leads_back_and_is_remembered() {
+    let app = common::app().await;
+    let page = app.post("/nsfw", &[("back", THREAD)]).await;
+
+    assert_eq!(page.status, StatusCode::SEE_OTHER);
+    assert_eq!(page.headers[header::LOCATION], THREAD);
+
+    let page = app.get_with_cookie(THREAD, &page.cookie()).await;
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("unsafe_enjoyer");
+    page.assert_not_contains("This page is marked as NSFW");
+}
+
+#[tokio::test]
+async fn consent_posted_from_other_sites_is_refused() {
+    let app = common::app().await;
+    let page = app
+        .post_from("https://evil.example", "/nsfw", &[("back", THREAD)])
+        .await;
+
+    assert_eq!(page.status, StatusCode::FORBIDDEN);
+    assert!(!page.headers.contains_key(header::SET_COOKIE));
+}
+
+#[tokio::test]
+async fn consent_from_browsers_without_origin_is_kept() {
+    // very old browsers send neither header, they're what /lite is for
+    let app = common::app().await;
+    let page = app.post_without_origin("/nsfw", &[("back", THREAD)]).await;
+
+    assert_eq!(page.status, StatusCode::SEE_OTHER);
+    assert!(page.headers.contains_key(header::SET_COOKIE));
+}
+
+#[tokio::test]
+async fn consent_only_leads_back_to_this_server() {
+    let app = common::app().await;
+
+    for back in [
+        "https://evil.example",
+        "//evil.example",
+        "/x\r\nSet-Cookie: prefs=forged",
+        "/caf\u{e9}",
+    ] {
+        let page = app.post("/nsfw", &[("back", back)]).await;
+
+        assert_eq!(page.status, StatusCode::SEE_OTHER, "{:?}", back);
+        assert_eq!(page.headers[header::LOCATION], "/", "{:?}", back);
+    }
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use axum::http::StatusCode;
+use older_reddit::api_result_types::{ApiData, RedditData, ReplyList};
+
+#[tokio::test]
+async fn posts_with_unexpected_fields_still_render() {
+    let app = common::app().await;
+    let page = app.get("/r/drift").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("Posts still show when fields change");
+}
+
+#[tokio::test]
+async fn comments_with_unexpected_fields_still_render() {
+    let app = common::app().await;
+    let page = app.get("/r/drift/comments/1drft1").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("Comments too");
+}
+
+#[tokio::test]
+async fn debug_schema_reports_what_couldnt_be_decoded() {
+    let app = common::app().await;
+    app.get("/r/drift/comments/1drft1").await;
+    app.get("/r/drift").await;
+
+    let page = app.get("/debug/schema").await;
+    assert_eq!(page.status, StatusCode::OK);
+
+    let report: serde_json::Value = serde_json::from_str(&page.body).unwrap();
+    let failed =
+        |kind: &str, field: &str| {
+            report["fields"].as_array().unwrap().iter().any(|f| {
+                f["kind"] == kind && f["field"] == field && f["failures"].as_u64() > Some(0)
+            })
+        };
+
+    assert!(failed("t3", "upvote_ratio"), "{}", page.body);
+    assert!(failed("t3", "subreddit_subscribers"), "{}", page.body);
+    assert!(failed("t3", "num_comments"), "{}", page.body);
+    assert!(failed("t1", "score"), "{}", page.body);
+    assert!(failed("t1", "is_submitter"), "{}", page.body);
+    assert!(
+        report["unknown_kinds"]["t9"].as_u64() > Some(0),
+        "{}",
+        page.body
+    );
+    assert!(
+        report["unknown_kinds"].get("more").is_none(),
+        "{}",
+        page.body
+    );
+}
+
+#[test]
+fn fields_of_another_shape_are_skipped_in_place() {
+    let json = r#"{"kind": "t3", "data": {
+        "title": {"nested": [1, {"deeper": true}]},
+        "score": [1, 2],
+        "url": {"not": "a string"},
+        "num_comments": -1,
+        "author": "kept",
+        "over_18": true
+    }}"#;
+
+    let Ok(ApiData::Single(RedditData::T3(post))) = serde_json::from_str(json) else {
+        panic!("the post should decode");
+    };
+    assert_eq!(post.title, "");
+    assert_eq!(post.score, 0);
+    assert_eq!(post.url, None);
+    assert_eq!(post.num_comments, 0);
+    assert_eq!(post.author, "kept");
+    assert!(post.over_18);
+}
+
+/// A chain of replies `depth` comments deep.
+fn reply_chain(depth: usize) -> String {
+    let mut replies = String::from(r#""""#);
+    for _ in 0..depth {
+        replies = format!(
+            r#"{{"kind": "Listing", "data": {{"children": [{{"kind": "t1", "data": {{"replies": {}}}}}]}}}}"#,
+            replies
+        );
+    }
+    replies
+}
+
+#[test]
+fn deep_threads_decode() {
+    assert!(serde_json::from_str::<ApiData>(&reply_chain(20)).is_ok());
+}
+
+#[test]
+fn endless_replies_are_cut_without_overflowing() {
+    assert!(serde_json::from_str::<ApiData>(&reply_chain(1_000)).is_ok());
+}
+
+#[test]
+fn replies_past_the_limit_are_cut_below_it() {
+    let Ok(ApiData::Single(RedditData::Listing(mut listing))) =
+        serde_json::from_str(&reply_chain(1_000))
+    else {
+        panic!("the thread should decode");
+    };
+
+    let mut depth = 0;
+    while let Some(RedditData::T1(comment)) = listing.children.pop() {
+        match comment.replies {
+            ReplyList::Replies(replies) => listing = replies,
+            ReplyList::None => break,
+        }
+        depth += 1;
+    }
+    assert_eq!(depth, 20);
+}
+
+#[tokio::test]
+async fn malformed_replies_only_lose_their_subtree() {
+    // the first comment has a reply without a kind, the second is fine
+    let json = r#"{"kind": "Listing", "data": {"children": [
+        {"kind": "t1", "data": {"body": "broken replies", "replies":
+            {"kind": "Listing", "data": {"children": [{"data": {"body": "no kind"}}]}}}},
+        {"kind": "t1", "data": {"body": "sibling", "replies": ""}}
+    ]}}"#;
+
+    let Ok(ApiData::Single(RedditData::Listing(listing))) = serde_json::from_str(json) else {
+        panic!("the thread should decode");
+    };
+    let bodies: Vec<_> = listing
+        .children
+        .iter()
+        .map(|c| match c {
+            RedditData::T1(t1) => (t1.body.as_str(), matches!(t1.replies, ReplyList::None)),
+            _ => panic!("expected comments"),
+        })
+        .collect();
+    assert_eq!(bodies, [("broken replies", true), ("sibling", true)]);
+
+    let app = common::app().await;
+    let page = app.get("/debug/schema").await;
+    let report: serde_json::Value = serde_json::from_str(&page.body).unwrap();
+    let replies_failed = report["fields"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|f| f["kind"] == "t1" && f["field"] == "replies");
+    assert!(replies_failed, "{}", page.body);
+}
+
+#[test]
+fn unparseable_replies_still_fail() {
+    let json = r#"{"kind": "t1", "data": {"replies": {"kind": "Listing", "data": ]}}"#;
+    assert!(serde_json::from_str::<ApiData>(json).is_err());
+}
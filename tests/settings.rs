@@ -0,0 +1,94 @@
+mod common;
+
+use axum::http::{header, StatusCode};
+
+/// The settings form as submitted with every default, `extra` added or in their place.
+fn form<'a>(extra: &[(&'a str, &'a str)]) -> Vec<(&'a str, &'a str)> {
+    let mut form = vec![
+        ("sort", "suggested"),
+        ("comment_sort", "suggested"),
+        ("search_sort", "relevance"),
+        ("search_time", "all"),
+        ("image_size", "medium"),
+        ("comment_depth", "0"),
+    ];
+    form.retain(|(name, _)| !extra.iter().any(|(e, _)| e == name));
+    form.extend_from_slice(extra);
+    form
+}
+
+#[tokio::test]
+async fn saving_settings_sets_the_cookie() {
+    let app = common::app().await;
+    let page = app.post("/settings", &form(&[("hide_nsfw", "on")])).await;
+
+    assert_eq!(page.status, StatusCode::SEE_OTHER);
+    assert!(page.headers.contains_key(header::SET_COOKIE));
+}
+
+#[tokio::test]
+async fn searches_default_to_the_saved_sort_and_time() {
+    let app = common::app().await;
+    let extra = [("search_sort", "new"), ("search_time", "week")];
+    let cookie = app.post("/settings", &form(&extra)).await.cookie();
+
+    let page = app.get_with_cookie("/r/rust/search?q=async", &cookie).await;
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("Announcing Rust 1.73.0");
+
+    // what's in the query still wins
+    let page = app
+        .get_with_cookie("/r/rust/search?q=async&sort=relevance&t=all", &cookie)
+        .await;
+    page.assert_contains("Why async Rust works the way it does");
+}
+
+#[tokio::test]
+async fn long_rules_are_cut() {
+    let app = common::app().await;
+    let keyword = "a".repeat(10_000);
+    let page = app
+        .post("/settings", &form(&[("filter_keywords", &keyword)]))
+        .await;
+
+    assert_eq!(page.status, StatusCode::SEE_OTHER);
+    let cookie = page.headers.get(header::SET_COOKIE).unwrap();
+    assert!(cookie.len() < 4096, "{} bytes", cookie.len());
+}
+
+#[tokio::test]
+async fn oversized_filters_are_refused_with_an_error() {
+    let app = common::app().await;
+    let rules: Vec<String> = (0..50)
+        .map(|i| format!("{}{}", i, "x".repeat(80)))
+        .collect();
+    let rules = rules.join("\n");
+    let extra = [
+        ("filter_subreddits", rules.as_str()),
+        ("filter_users", rules.as_str()),
+        ("filter_domains", rules.as_str()),
+        ("filter_keywords", rules.as_str()),
+        ("filter_flairs", rules.as_str()),
+    ];
+    let page = app.post("/settings", &form(&extra)).await;
+
+    assert_eq!(page.status, StatusCode::BAD_REQUEST);
+    assert!(!page.headers.contains_key(header::SET_COOKIE));
+    page.assert_contains("Too many filters to save");
+    // what was submitted is kept, to be trimmed
+    page.assert_contains("49xxxx");
+}
+
+#[tokio::test]
+async fn settings_posted_from_other_sites_are_refused() {
+    let app = common::app().await;
+    let page = app
+        .post_from("https://evil.example", "/settings", &form(&[]))
+        .await;
+
+    assert_eq!(page.status, StatusCode::FORBIDDEN);
+    assert!(!page.headers.contains_key(header::SET_COOKIE));
+
+    let page = app.post_from("null", "/settings", &form(&[])).await;
+    assert_eq!(page.status, StatusCode::FORBIDDEN);
+}
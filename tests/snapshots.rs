@@ -0,0 +1,157 @@
+mod common;
+
+// Every page type rendered from the fixtures, compared with the pages saved in `tests/snapshots`.
+// After changing a template on purpose, save them again and review the diff:
+//
+//     UPDATE_SNAPSHOTS=1 cargo test --test snapshots
+
+use axum::http::StatusCode;
+
+const SNAPSHOTS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots");
+
+/// Indentation and blank lines are left out, templates are full of them and they don't show.
+/// So are the versions of the themes, or every page would change with the CSS.
+fn normalize(html: &str) -> String {
+    html.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|l| format!("{}\n", without_theme_version(l)))
+        .collect()
+}
+
+fn without_theme_version(line: &str) -> String {
+    match line.split_once(".css?v=") {
+        Some((before, after)) => {
+            let rest = after.find('"').map_or("", |i| &after[i..]);
+            format!("{}.css{}", before, rest)
+        }
+        None => line.to_string(),
+    }
+}
+
+/// Renders `path` and compares it with the snapshot called `name`.
+async fn assert_snapshot(name: &str, path: &str) {
+    let app = common::app().await;
+    let page = app.get(path).await;
+    assert_eq!(page.status, StatusCode::OK, "{}", page.body);
+
+    let html = normalize(&page.body);
+    let file = format!("{}/{}.html", SNAPSHOTS, name);
+
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&file, &html).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&file).unwrap_or_else(|_| {
+        panic!(
+            "no snapshot {}, save it with UPDATE_SNAPSHOTS=1 cargo test --test snapshots",
+            file
+        )
+    });
+
+    if html == expected {
+        return;
+    }
+
+    let (line, (expected_line, actual_line)) = expected
+        .lines()
+        .chain(std::iter::repeat(""))
+        .zip(html.lines().chain(std::iter::repeat("")))
+        .enumerate()
+        .find(|(_, (e, a))| e != a)
+        .unwrap();
+
+    panic!(
+        "{} differs from {} at line {}:\n- {}\n+ {}\n\nthe whole page:\n{}\n\
+         if the change is intended, save it with UPDATE_SNAPSHOTS=1 cargo test --test snapshots",
+        path,
+        file,
+        line + 1,
+        expected_line,
+        actual_line,
+        html
+    );
+}
+
+#[tokio::test]
+async fn subreddit_with_every_post_type() {
+    // text (stickied, flaired), link (author flair), gallery, poll, nsfw
+    assert_snapshot("subreddit", "/r/rust").await;
+}
+
+#[tokio::test]
+async fn subreddit_with_every_post_state() {
+    // pinned, locked and stickied, video, image (flaired), spoiler
+    assert_snapshot("subreddit_states", "/r/crabs").await;
+}
+
+#[tokio::test]
+async fn empty_subreddit() {
+    assert_snapshot("subreddit_empty", "/r/empty").await;
+}
+
+#[tokio::test]
+async fn text_post_with_nested_replies() {
+    assert_snapshot("comments_text", "/r/rust/comments/1abcde").await;
+}
+
+#[tokio::test]
+async fn gallery_post() {
+    assert_snapshot("comments_gallery", "/r/rust/comments/1gal01").await;
+}
+
+#[tokio::test]
+async fn poll_post_without_comments() {
+    assert_snapshot("comments_poll", "/r/rust/comments/1poll1").await;
+}
+
+#[tokio::test]
+async fn video_post() {
+    assert_snapshot("comments_video", "/r/crabs/comments/1vid01").await;
+}
+
+#[tokio::test]
+async fn image_post() {
+    assert_snapshot("comments_image", "/r/crabs/comments/1img01").await;
+}
+
+#[tokio::test]
+async fn locked_post_with_locked_and_stickied_comments() {
+    assert_snapshot("comments_locked", "/r/crabs/comments/1lock1").await;
+}
+
+#[tokio::test]
+async fn user_with_posts_and_comments() {
+    assert_snapshot("user", "/u/spez").await;
+}
+
+#[tokio::test]
+async fn user_without_posts() {
+    assert_snapshot("user_empty", "/u/quiet").await;
+}
+
+#[tokio::test]
+async fn search_results() {
+    assert_snapshot("search", "/r/rust/search?q=async").await;
+}
+
+#[tokio::test]
+async fn search_without_results() {
+    assert_snapshot("search_empty", "/r/rust/search?q=nothing").await;
+}
+
+#[tokio::test]
+async fn wiki_page() {
+    assert_snapshot("wiki", "/r/rust/wiki").await;
+}
+
+#[tokio::test]
+async fn wiki_page_index() {
+    assert_snapshot("wiki_pages", "/r/rust/wiki/pages").await;
+}
+
+#[tokio::test]
+async fn wiki_revisions() {
+    assert_snapshot("wiki_revisions", "/r/rust/wiki/revisions").await;
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use axum::http::{header, StatusCode};
+
+#[tokio::test]
+async fn listing_shows_every_kind_of_post() {
+    let app = common::app().await;
+    let page = app.get("/r/rust").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("<h1 class=\"subreddit-name\">r/rust</h1>");
+    page.assert_contains("<a href=\"/r/rust/comments/1abcde\">Announcing Rust 1.73.0</a>");
+    page.assert_contains("Why async Rust works the way it does");
+    page.assert_contains("Ferris plushies from RustConf");
+    page.assert_contains("Which editor do you use for Rust?");
+}
+
+#[tokio::test]
+async fn listing_marks_stickies_and_flairs() {
+    let app = common::app().await;
+    let page = app.get("/r/rust").await;
+
+    page.assert_contains("subreddit-post-stickied");
+    page.assert_contains(">announcement</div>");
+    page.assert_contains(">rustc contributor</div>");
+}
+
+#[tokio::test]
+async fn thumbnails_go_through_the_proxy() {
+    let app = common::app().await;
+    let page = app.get("/r/rust").await;
+
+    page.assert_contains("/t/qR7mLw2cX4k.jpg");
+    page.assert_not_contains("b.thumbs.redditmedia.com");
+}
+
+#[tokio::test]
+async fn nsfw_posts_are_tagged() {
+    let app = common::app().await;
+    let page = app.get("/r/rust").await;
+
+    page.assert_contains("Cursed unsafe code");
+    page.assert_contains("<div class=\"nsfw\">NSFW</div>");
+}
+
+#[tokio::test]
+async fn about_shows_sidebar_and_rules() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/about").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("The Rust Programming Language");
+    page.assert_contains("A place for all things related to the Rust programming language.");
+    page.assert_contains("On-topic");
+    page.assert_contains("Be civil");
+}
+
+#[tokio::test]
+async fn search_shows_results() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/search?q=async").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("Why async Rust works the way it does");
+    page.assert_not_contains("Announcing Rust 1.73.0");
+}
+
+#[tokio::test]
+async fn legacy_layout_renders_the_same_listing() {
+    let app = common::app().await;
+    let page = app.get("/lite/r/rust").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("Announcing Rust 1.73.0");
+}
+
+#[tokio::test]
+async fn atom_ids_dont_depend_on_the_host() {
+    let app = common::app().await;
+    let page = app.get("/r/rust.atom").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("<id>tag:reddit.com,2005:/r/rust</id>");
+    page.assert_contains("<id>tag:reddit.com,2005:t3_1abcde</id>");
+    page.assert_not_contains("<id>http");
+}
+
+#[tokio::test]
+async fn feeds_link_to_the_host_they_were_asked_on() {
+    let app = common::app().await;
+    let headers = [
+        ("host", "reddit.example.org"),
+        ("x-forwarded-proto", "https"),
+    ];
+    let page = app.get_with_headers("/r/rust.rss", &headers).await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    assert_eq!(page.headers[header::CONTENT_TYPE], "application/rss+xml");
+    page.assert_contains("<link>https://reddit.example.org/r/rust</link>");
+    page.assert_contains("<link>https://reddit.example.org/r/rust/comments/1abcde</link>");
+}
+
+#[tokio::test]
+async fn json_api_lists_posts() {
+    let app = common::app().await;
+    let page = app.get("/api/v1/r/rust").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    let json: serde_json::Value = serde_json::from_str(&page.body).unwrap();
+    assert_eq!(json["items"][0]["id"], "1abcde");
+    assert!(json["next"].is_string());
+}
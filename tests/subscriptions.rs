@@ -0,0 +1,54 @@
+mod common;
+
+use axum::http::{header, StatusCode};
+
+#[tokio::test]
+async fn subscribing_sets_the_cookie() {
+    let app = common::app().await;
+    let page = app.post("/r/rust/subscribe", &[]).await;
+
+    assert_eq!(page.status, StatusCode::SEE_OTHER);
+    assert!(page.headers.contains_key(header::SET_COOKIE));
+}
+
+#[tokio::test]
+async fn subscriptions_posted_from_other_sites_are_refused() {
+    let app = common::app().await;
+
+    for path in [
+        "/r/rust/subscribe",
+        "/r/rust/unsubscribe",
+        "/subscriptions/import",
+    ] {
+        let page = app.post_from("https://evil.example", path, &[]).await;
+
+        assert_eq!(page.status, StatusCode::FORBIDDEN, "{}", path);
+        assert!(!page.headers.contains_key(header::SET_COOKIE), "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn invalid_names_are_refused() {
+    let app = common::app().await;
+
+    for path in ["/r/a%0D%0A/subscribe", "/r/a%0D%0A/unsubscribe"] {
+        let page = app.post(path, &[]).await;
+
+        assert_eq!(page.status, StatusCode::BAD_REQUEST, "{}", path);
+        assert!(!page.headers.contains_key(header::SET_COOKIE), "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn unsubscribing_only_leads_back_to_this_server() {
+    let app = common::app().await;
+    let page = app
+        .post(
+            "/r/rust/unsubscribe",
+            &[("back", "/r/rust\r\nSet-Cookie: subscriptions=forged")],
+        )
+        .await;
+
+    assert_eq!(page.status, StatusCode::SEE_OTHER);
+    assert_eq!(page.headers[header::LOCATION], "/r/rust");
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use axum::http::StatusCode;
+
+#[tokio::test]
+async fn profile_shows_karma_trophies_and_activity() {
+    let app = common::app().await;
+    let page = app.get("/u/spez").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("spez");
+    page.assert_contains("Reddit Admin");
+    page.assert_contains("18-Year Club");
+    page.assert_contains("Update on the API");
+    page.assert_contains("making some changes to the API.");
+}
+
+#[tokio::test]
+async fn deleted_accounts_only_show_their_name() {
+    let app = common::app().await;
+    let page = app.get("/u/deleted_account").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("deleted_account");
+    page.assert_contains("deleted or shadowbanned");
+}
+
+#[tokio::test]
+async fn profiles_show_without_their_header_when_it_fails() {
+    let app = common::app().await;
+    let page = app.get("/u/flaky").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("flaky");
+    page.assert_not_contains("karma");
+    page.assert_not_contains("deleted or shadowbanned");
+}
+
+#[tokio::test]
+async fn activity_feeds_have_posts_and_comments() {
+    let app = common::app().await;
+    let page = app.get("/u/spez.atom").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("<id>tag:reddit.com,2005:t3_1anno5</id>");
+    page.assert_contains("<id>tag:reddit.com,2005:t1_k6u0001</id>");
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use axum::http::StatusCode;
+
+#[tokio::test]
+async fn index_renders_markdown() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/wiki").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("Welcome");
+    page.assert_contains("<table>");
+    page.assert_contains("rust_mod");
+}
+
+#[tokio::test]
+async fn pages_are_found_by_name() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/wiki/faq").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("Is Rust ready for production?");
+}
+
+#[tokio::test]
+async fn page_list_links_every_page() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/wiki/pages").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("/r/rust/wiki/config/sidebar");
+    page.assert_contains("/r/rust/wiki/faq");
+}
+
+#[tokio::test]
+async fn revisions_show_reasons() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/wiki/revisions").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("clarify the table");
+}
+
+#[tokio::test]
+async fn pages_named_like_revisions_are_pages() {
+    let app = common::app().await;
+    let page = app.get("/r/rust/wiki/revisions_policy").await;
+
+    assert_eq!(page.status, StatusCode::OK);
+    page.assert_contains("Reverting edits");
+}